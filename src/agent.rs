@@ -0,0 +1,335 @@
+use super::{cache, crawler, entry, options, source, thread};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not start agent: {0}")]
+    Spawn(std::io::Error),
+    #[error("Could not send frame: {0}")]
+    Send(std::io::Error),
+    #[error("Could not receive frame: {0}")]
+    Receive(std::io::Error),
+    #[error("Could not encode frame: {0}")]
+    Encode(bincode::Error),
+    #[error("Could not decode frame: {0}")]
+    Decode(bincode::Error),
+    #[error("Frame of {0} bytes is too large")]
+    TooLarge(u32),
+    #[error("Agent speaks protocol version {0}, expected {VERSION}")]
+    Version(u32),
+    #[error("Unexpected frame from agent")]
+    Unexpected,
+    #[error("Agent closed the connection")]
+    Closed,
+    #[error("Agent failed: {0}")]
+    Remote(String),
+    #[error("Agent sent a path outside of its directory: `{0}`")]
+    Path(std::path::PathBuf),
+    #[error("Agent exited with {0}")]
+    Exit(std::process::ExitStatus),
+}
+
+/// Bumped whenever the frames change, so both ends can tell they do not understand each other
+const VERSION: u32 = 3;
+
+/// Frames are prefixed with their length, which is capped so a corrupted prefix cannot make the
+/// other end allocate without bounds
+const LIMIT: u32 = 256 * 1024 * 1024;
+
+/// How often the agent reports how far hashing got
+const PROGRESS: std::time::Duration = std::time::Duration::from_millis(250);
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum Request {
+    /// Count the files to hash, answered with a [`Response::Listing`]
+    List,
+    /// Hash the directory, answered with [`Response::Progress`] and [`Response::Entry`] frames
+    /// up to a [`Response::Done`]
+    Hash,
+    Quit,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum Response {
+    /// Sent as soon as the agent starts, with the directory it serves
+    Hello {
+        version: u32,
+        path: std::path::PathBuf,
+    },
+    Listing {
+        files: u64,
+        bytes: u64,
+    },
+    /// How many files and bytes were hashed so far
+    Progress {
+        files: u64,
+        bytes: u64,
+    },
+    Entry(entry::Entry),
+    /// Every entry was sent
+    Done {
+        directories: Vec<std::path::PathBuf>,
+        settings: options::Settings,
+    },
+    /// The request failed, the agent keeps serving
+    Error(String),
+}
+
+/// Serves the directory at `path` to a client reading `writer` and writing `reader`, usually the
+/// standard output and input of `dircmp agent`, until it quits or closes `reader`
+///
+/// The client is expected to be [`index_remote`], which asks for the directory to be listed and
+/// then hashed with the given [`Options`](options::Options)
+///
+/// # Errors
+///
+/// If the client cannot be read from or written to. Errors while indexing are sent to the client
+/// instead
+pub fn serve(
+    path: &std::path::Path,
+    options: &options::Options,
+    mut reader: impl std::io::Read,
+    writer: impl std::io::Write + Send,
+) -> Result<(), super::Error> {
+    let writer = std::sync::Mutex::new(writer);
+    send(
+        &writer,
+        &Response::Hello {
+            version: VERSION,
+            path: path.to_path_buf(),
+        },
+    )?;
+
+    while let Some(request) = receive(&mut reader)? {
+        log::debug!("Received {request:?}");
+        match request {
+            Request::List => match list(path) {
+                Ok((files, bytes)) => send(&writer, &Response::Listing { files, bytes })?,
+                Err(e) => send(&writer, &Response::Error(e.to_string()))?,
+            },
+            Request::Hash => hash(path, options, &writer)?,
+            Request::Quit => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// Indexes the directory served by the agent `command` starts, e.g. `dircmp agent` on another
+/// host over ssh, as if it was a manifest
+///
+/// The agent hashes with its own options, which its directory records in its
+/// [`settings`](entry::Directory::settings)
+///
+/// # Errors
+///
+/// If the agent cannot be started, fails or does not follow the protocol
+pub fn index_remote(mut command: std::process::Command) -> Result<entry::Directory, super::Error> {
+    let mut child = command
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::inherit())
+        .spawn()
+        .map_err(Error::Spawn)?;
+    let (Some(input), Some(output)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err(Error::Spawn(std::io::Error::other("stdio was not captured")).into());
+    };
+
+    let result = client(
+        std::sync::Mutex::new(input),
+        std::io::BufReader::new(output),
+    );
+    let status = child.wait().map_err(Error::Spawn)?;
+    match result {
+        Ok(_) | Err(Error::Closed) if !status.success() => Err(Error::Exit(status).into()),
+        result => Ok(result?),
+    }
+}
+
+fn client(
+    input: std::sync::Mutex<std::process::ChildStdin>,
+    mut output: impl std::io::Read,
+) -> Result<entry::Directory, Error> {
+    let mut next = || receive::<Response>(&mut output)?.ok_or(Error::Closed);
+
+    let path = match next()? {
+        Response::Hello { version, path } if version == VERSION => path,
+        Response::Hello { version, .. } => return Err(Error::Version(version)),
+        _ => return Err(Error::Unexpected),
+    };
+    log::info!("Indexing {} through agent", path.display());
+    let start = std::time::Instant::now();
+
+    send(&input, &Request::List)?;
+    let (total, total_bytes) = match next()? {
+        Response::Listing { files, bytes } => (files, bytes),
+        Response::Error(e) => return Err(Error::Remote(e)),
+        _ => return Err(Error::Unexpected),
+    };
+    log::info!("Agent found {total} files, {total_bytes} bytes");
+
+    send(&input, &Request::Hash)?;
+    let mut entries = Vec::new();
+    let (directories, settings) = loop {
+        match next()? {
+            Response::Progress { files, bytes } => {
+                log::info!("Agent hashed {files}/{total} files, {bytes}/{total_bytes} bytes");
+            }
+            Response::Entry(entry) => {
+                if !is_relative(&entry.path) {
+                    return Err(Error::Path(entry.path));
+                }
+                entries.push(entry);
+            }
+            Response::Done {
+                directories,
+                settings,
+            } => break (directories, settings),
+            Response::Error(e) => return Err(Error::Remote(e)),
+            Response::Hello { .. } | Response::Listing { .. } => return Err(Error::Unexpected),
+        }
+    };
+    if let Some(path) = directories.iter().find(|path| !is_relative(path)) {
+        return Err(Error::Path(path.clone()));
+    }
+    send(&input, &Request::Quit)?;
+    drop(input);
+
+    log::info!(
+        "Finished indexing {} items for {} through agent in {:?}",
+        entries.len(),
+        path.display(),
+        start.elapsed(),
+    );
+
+    entries.sort_unstable();
+    let mut directory = entry::Directory::new(path, entries, directories, settings);
+    directory.live = false;
+    Ok(directory)
+}
+
+/// Counts the files under `path` and their size, the way the crawler walks them
+fn list(path: &std::path::Path) -> std::io::Result<(u64, u64)> {
+    use source::FileSystem;
+
+    let mut pending = vec![path.to_path_buf()];
+    let (mut files, mut bytes) = (0, 0);
+    while let Some(directory) = pending.pop() {
+        for path in source::Local.read_dir(&directory)? {
+            let Ok(metadata) = source::Local.metadata(&path) else {
+                continue;
+            };
+            if metadata.kind == entry::Kind::Directory {
+                pending.push(path);
+            } else {
+                files += 1;
+                bytes += metadata.size;
+            }
+        }
+    }
+    Ok((files, bytes))
+}
+
+fn hash(
+    path: &std::path::Path,
+    options: &options::Options,
+    writer: &std::sync::Mutex<impl std::io::Write + Send>,
+) -> Result<(), Error> {
+    let state = std::sync::Mutex::new((0, 0, std::time::Instant::now()));
+    let progress = |entry: &entry::Entry| {
+        let Ok(mut state) = state.lock() else {
+            return;
+        };
+        let (files, bytes, last) = &mut *state;
+        *files += 1;
+        *bytes += entry.size;
+        if last.elapsed() >= PROGRESS {
+            *last = std::time::Instant::now();
+            let progress = Response::Progress {
+                files: *files,
+                bytes: *bytes,
+            };
+            if let Err(e) = send(writer, &progress) {
+                log::warn!("Failed to send progress: {e}");
+            }
+        }
+    };
+
+    let indexed = thread::pool().map_err(super::Error::from).and_then(|pool| {
+        let cache = cache::Cache::open(options);
+        let indexed = crawler::crawl(
+            &std::sync::Arc::new(source::Local),
+            path,
+            options,
+            cache.as_ref(),
+            Some(&progress),
+            &pool,
+        )?;
+        if let Some(cache) = cache {
+            cache.save()?;
+        }
+        Ok(indexed)
+    });
+
+    let (entries, directories) = match indexed {
+        Ok(indexed) => indexed,
+        Err(e) => return send(writer, &Response::Error(e.to_string())),
+    };
+    for entry in entries {
+        send(writer, &Response::Entry(entry))?;
+    }
+    send(
+        writer,
+        &Response::Done {
+            directories,
+            settings: options.into(),
+        },
+    )
+}
+
+/// Whether `path` stays within the directory it is relative to
+fn is_relative(path: &std::path::Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, std::path::Component::Normal(_)))
+}
+
+fn send(
+    writer: &std::sync::Mutex<impl std::io::Write>,
+    frame: &impl serde::Serialize,
+) -> Result<(), Error> {
+    let frame = bincode::serialize(frame).map_err(Error::Encode)?;
+    let length = u32::try_from(frame.len()).unwrap_or(u32::MAX);
+    if length > LIMIT {
+        return Err(Error::TooLarge(length));
+    }
+
+    let mut writer = writer
+        .lock()
+        .map_err(|_| Error::Send(std::io::Error::other("writer lock poisoned")))?;
+    writer
+        .write_all(&length.to_be_bytes())
+        .and_then(|()| writer.write_all(&frame))
+        .and_then(|()| writer.flush())
+        .map_err(Error::Send)
+}
+
+/// Reads the next frame, or `None` if the other end closed the stream in between frames
+fn receive<T: serde::de::DeserializeOwned>(
+    reader: &mut impl std::io::Read,
+) -> Result<Option<T>, Error> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(Error::Receive(e)),
+    }
+
+    let length = u32::from_be_bytes(length);
+    if length > LIMIT {
+        return Err(Error::TooLarge(length));
+    }
+    let mut frame = vec![0; length as usize];
+    reader.read_exact(&mut frame).map_err(Error::Receive)?;
+    bincode::deserialize(&frame)
+        .map(Some)
+        .map_err(Error::Decode)
+}
@@ -0,0 +1,79 @@
+use super::entry::Hash;
+
+/// The digest used to hash file contents
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Algorithm {
+    #[default]
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    /// Finds the algorithm producing digests of `length` bytes
+    #[must_use]
+    pub fn from_length(length: usize) -> Option<Self> {
+        match length {
+            16 => Some(Self::Md5),
+            32 => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+
+    /// The length of the digests in bytes
+    #[must_use]
+    pub fn length(self) -> usize {
+        match self {
+            Self::Md5 => 16,
+            Self::Sha256 => 32,
+        }
+    }
+
+    pub(crate) fn hasher(self) -> Hasher {
+        use md5::Digest;
+
+        match self {
+            Self::Md5 => Hasher::Md5(md5::Md5::new()),
+            Self::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+        }
+    }
+
+    pub(crate) fn digest(self, bytes: &[u8]) -> Hash {
+        let mut hasher = self.hasher();
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+}
+
+impl std::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Md5 => f.write_str("MD5"),
+            Self::Sha256 => f.write_str("SHA256"),
+        }
+    }
+}
+
+pub(crate) enum Hasher {
+    Md5(md5::Md5),
+    Sha256(sha2::Sha256),
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        use md5::Digest;
+
+        match self {
+            Self::Md5(hasher) => hasher.update(bytes),
+            Self::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    pub(crate) fn finalize(self) -> Hash {
+        use md5::Digest;
+
+        match self {
+            Self::Md5(hasher) => Hash::new(hasher.finalize().into()),
+            Self::Sha256(hasher) => Hash::new(hasher.finalize().into()),
+        }
+    }
+}
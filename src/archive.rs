@@ -0,0 +1,503 @@
+use super::{crawler, entry, options};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not open archive {0}: {1}")]
+    Open(std::path::PathBuf, std::io::Error),
+    #[error("Could not read archive {0}: {1}")]
+    Read(std::path::PathBuf, std::io::Error),
+    #[error("Could not read zip archive {0}: {1}")]
+    Zip(std::path::PathBuf, zip::result::ZipError),
+    #[error("Unsupported archive {0}")]
+    Unsupported(std::path::PathBuf),
+    #[error(transparent)]
+    Hash(#[from] crawler::Error),
+}
+
+/// The entries and directories of an archive, relative to its root
+type Members = (Vec<entry::Entry>, Vec<std::path::PathBuf>);
+
+/// How the members of an archive are stored
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Format {
+    Tar,
+    TarGzip,
+    TarZstd,
+    Zip,
+}
+
+impl Format {
+    fn detect(path: &std::path::Path) -> Option<Self> {
+        const SUFFIXES: [(&str, Format); 17] = [
+            (".tar", Format::Tar),
+            (".tar.gz", Format::TarGzip),
+            (".tgz", Format::TarGzip),
+            (".tar.zst", Format::TarZstd),
+            (".tzst", Format::TarZstd),
+            (".zip", Format::Zip),
+            (".jar", Format::Zip),
+            (".war", Format::Zip),
+            (".ear", Format::Zip),
+            (".apk", Format::Zip),
+            (".epub", Format::Zip),
+            (".docx", Format::Zip),
+            (".xlsx", Format::Zip),
+            (".pptx", Format::Zip),
+            (".odt", Format::Zip),
+            (".ods", Format::Zip),
+            (".odp", Format::Zip),
+        ];
+
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        SUFFIXES
+            .iter()
+            .find(|(suffix, _)| name.ends_with(suffix))
+            .map(|(_, format)| *format)
+    }
+}
+
+/// Whether the file at `path` is an archive that can be indexed in place of a directory, i.e. a
+/// `.tar`, `.tar.gz`, `.tar.zst` or zip-based file such as `.zip`, `.jar` or `.docx`
+#[must_use]
+pub fn is_archive(path: &std::path::Path) -> bool {
+    path.is_file() && has_archive_name(path)
+}
+
+/// Whether `path` is named like an archive, whatever file system it is on
+pub(crate) fn has_archive_name(path: &std::path::Path) -> bool {
+    Format::detect(path).is_some()
+}
+
+/// Indexes the members of the archive at `path` as the entries of a directory, hashing them as
+/// they are read out of the archive without extracting them
+pub fn read(
+    path: &std::path::Path,
+    options: &options::Options,
+) -> Result<entry::Directory, super::Error> {
+    log::info!("Indexing {}", path.display());
+    let start = std::time::Instant::now();
+
+    let file = std::fs::File::open(path).map_err(|e| Error::Open(path.to_path_buf(), e))?;
+    let (entries, directories) = expand(file, path, options)?;
+
+    log::info!(
+        "Finished indexing {} items for {} in {:?}",
+        entries.len(),
+        path.display(),
+        start.elapsed(),
+    );
+
+    let mut directory =
+        entry::Directory::new(path.to_path_buf(), entries, directories, options.into());
+    directory.live = false;
+    Ok(directory)
+}
+
+/// Lists the entries and directories of the archive read from `reader`, relative to its root, the
+/// format being told by the name of `path`
+///
+/// Symlinks take the hash of the member they point to, as the crawler follows them, and are
+/// skipped if it is not in the archive. A member stored several times is indexed as its last copy,
+/// which is the one extraction leaves behind. With [`into_archives`](options::Options::into_archives)
+/// nested archives are expanded in turn, their members listed under their path
+pub fn expand(
+    reader: impl std::io::Read + std::io::Seek,
+    path: &std::path::Path,
+    options: &options::Options,
+) -> Result<Members, Error> {
+    let format = Format::detect(path).ok_or_else(|| Error::Unsupported(path.to_path_buf()))?;
+    members(format, std::io::BufReader::new(reader), path, options)
+}
+
+fn members(
+    format: Format,
+    reader: impl std::io::BufRead + std::io::Seek,
+    path: &std::path::Path,
+    options: &options::Options,
+) -> Result<Members, Error> {
+    let listing = match format {
+        Format::Tar => tar(reader, path, options)?,
+        Format::TarGzip => tar(flate2::bufread::MultiGzDecoder::new(reader), path, options)?,
+        Format::TarZstd => tar(
+            zstd::Decoder::with_buffer(reader).map_err(|e| Error::Open(path.to_path_buf(), e))?,
+            path,
+            options,
+        )?,
+        Format::Zip => zip(reader, path, options)?,
+    };
+    Ok(listing.finish(path))
+}
+
+fn tar(
+    reader: impl std::io::Read,
+    path: &std::path::Path,
+    options: &options::Options,
+) -> Result<Listing, Error> {
+    let read = |e| Error::Read(path.to_path_buf(), e);
+
+    let mut listing = Listing::default();
+    let mut archive = tar::Archive::new(reader);
+    for member in archive.entries().map_err(read)? {
+        let mut member = member.map_err(read)?;
+        let Some(name) = relative(&member.path().map_err(read)?) else {
+            log::warn!(
+                "Skipping `{}` in {}, it is outside of the archive",
+                String::from_utf8_lossy(&member.path_bytes()),
+                path.display()
+            );
+            continue;
+        };
+
+        let kind = member.header().entry_type();
+        if kind.is_dir() {
+            listing.directory(name);
+        } else if kind.is_symlink() || kind.is_hard_link() {
+            let Some(target) = member.link_name().map_err(read)? else {
+                continue;
+            };
+            if kind.is_symlink() {
+                listing.symlink(name, &target);
+            } else {
+                listing.hardlink(name, &target);
+            }
+        } else if kind.is_file() || kind.is_contiguous() {
+            let prefix = match nested(&name, &mut member, path, options)? {
+                Nested::Expanded(members) => {
+                    listing.nested(&name, members);
+                    continue;
+                }
+                Nested::File(prefix) => prefix,
+            };
+
+            let modified = member
+                .header()
+                .mtime()
+                .ok()
+                .map(|secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+            let attributes = if options.attributes {
+                Some(attributes(&mut member).map_err(read)?)
+            } else {
+                None
+            };
+            let (hash, raw, size) = crawler::hash_reader(
+                &mut std::io::Read::chain(prefix.as_slice(), &mut member),
+                &name,
+                options,
+            )?;
+            listing.file(entry::Entry::new(
+                name,
+                hash,
+                raw,
+                entry::Kind::File,
+                size,
+                modified,
+                attributes,
+            ));
+        }
+    }
+
+    Ok(listing)
+}
+
+fn zip(
+    reader: impl std::io::Read + std::io::Seek,
+    path: &std::path::Path,
+    options: &options::Options,
+) -> Result<Listing, Error> {
+    use std::io::Read;
+
+    let mut listing = Listing::default();
+    let mut archive =
+        zip::ZipArchive::new(reader).map_err(|e| Error::Zip(path.to_path_buf(), e))?;
+    for index in 0..archive.len() {
+        let mut member = archive
+            .by_index(index)
+            .map_err(|e| Error::Zip(path.to_path_buf(), e))?;
+        let Some(name) = member.enclosed_name() else {
+            log::warn!(
+                "Skipping `{}` in {}, it is outside of the archive",
+                member.name(),
+                path.display()
+            );
+            continue;
+        };
+
+        if member.is_dir() {
+            listing.directory(name);
+        } else if member.is_symlink() {
+            let mut target = Vec::new();
+            member
+                .read_to_end(&mut target)
+                .map_err(|e| Error::Read(path.to_path_buf(), e))?;
+            listing.symlink(
+                name,
+                std::path::Path::new(&*String::from_utf8_lossy(&target)),
+            );
+        } else {
+            let prefix = match nested(&name, &mut member, path, options)? {
+                Nested::Expanded(members) => {
+                    listing.nested(&name, members);
+                    continue;
+                }
+                Nested::File(prefix) => prefix,
+            };
+
+            let modified = modified(&member);
+            let (hash, raw, size) = crawler::hash_reader(
+                &mut std::io::Read::chain(prefix.as_slice(), &mut member),
+                &name,
+                options,
+            )?;
+            listing.file(entry::Entry::new(
+                name,
+                hash,
+                raw,
+                entry::Kind::File,
+                size,
+                modified,
+                // Zip archives have nowhere to store extended attributes
+                options
+                    .attributes
+                    .then(|| entry::Attributes::new(Vec::new())),
+            ));
+        }
+    }
+
+    Ok(listing)
+}
+
+/// The largest nested archive read into memory to be expanded, larger ones are hashed whole
+const NESTED_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// What became of a member whose name may be the one of an archive
+enum Nested {
+    /// The member was expanded into its own members
+    Expanded(Members),
+    /// The member is to be hashed as a file, starting with the content already read from it
+    File(Vec<u8>),
+}
+
+/// Expands the member `name` if it is itself an archive and archives are to be recursed into
+///
+/// Zip archives need to seek and a member can only be read once, so nested archives are read
+/// into memory whole, up to [`NESTED_LIMIT`]. Larger ones, and ones that cannot be read as
+/// archives, are hashed as plain files instead, with a warning
+fn nested(
+    name: &std::path::Path,
+    member: &mut impl std::io::Read,
+    path: &std::path::Path,
+    options: &options::Options,
+) -> Result<Nested, Error> {
+    let Some(format) = Format::detect(name).filter(|_| options.into_archives) else {
+        return Ok(Nested::File(Vec::new()));
+    };
+
+    let mut content = Vec::new();
+    let mut limited = std::io::Read::take(&mut *member, NESTED_LIMIT + 1);
+    std::io::Read::read_to_end(&mut limited, &mut content)
+        .map_err(|e| Error::Read(path.to_path_buf(), e))?;
+    let nested = path.join(name);
+    if content.len() as u64 > NESTED_LIMIT {
+        log::warn!(
+            "Hashing nested archive {} as a file, it is larger than {NESTED_LIMIT} bytes",
+            nested.display()
+        );
+        return Ok(Nested::File(content));
+    }
+
+    match members(
+        format,
+        std::io::Cursor::new(content.as_slice()),
+        &nested,
+        options,
+    ) {
+        Ok(members) => Ok(Nested::Expanded(members)),
+        Err(e) => {
+            log::warn!("Hashing nested archive {} as a file: {e}", nested.display());
+            Ok(Nested::File(content))
+        }
+    }
+}
+
+/// The members of an archive, keyed by their path relative to its root, where later members
+/// replace earlier ones of the same path
+#[derive(Default)]
+struct Listing {
+    files: std::collections::BTreeMap<std::path::PathBuf, entry::Entry>,
+    symlinks: std::collections::BTreeMap<std::path::PathBuf, Option<std::path::PathBuf>>,
+    hardlinks: std::collections::BTreeMap<std::path::PathBuf, Option<std::path::PathBuf>>,
+    directories: std::collections::BTreeSet<std::path::PathBuf>,
+}
+
+impl Listing {
+    fn directory(&mut self, name: std::path::PathBuf) {
+        self.parents(&name);
+        if !name.as_os_str().is_empty() {
+            self.directories.insert(name);
+        }
+    }
+
+    fn file(&mut self, entry: entry::Entry) {
+        self.parents(&entry.path);
+        self.symlinks.remove(&entry.path);
+        self.hardlinks.remove(&entry.path);
+        self.files.insert(entry.path.clone(), entry);
+    }
+
+    /// Symlink targets are relative to the directory of the symlink
+    fn symlink(&mut self, name: std::path::PathBuf, target: &std::path::Path) {
+        self.parents(&name);
+        self.files.remove(&name);
+        self.hardlinks.remove(&name);
+        let target = relative(&name.parent().unwrap_or(&name).join(target));
+        self.symlinks.insert(name, target);
+    }
+
+    /// Hardlink targets are relative to the root of the archive
+    fn hardlink(&mut self, name: std::path::PathBuf, target: &std::path::Path) {
+        self.parents(&name);
+        self.files.remove(&name);
+        self.symlinks.remove(&name);
+        self.hardlinks.insert(name, relative(target));
+    }
+
+    /// Lists the members of the nested archive `name` as its content
+    ///
+    /// Hardlink groups are numbered per archive, so links within nested archives are dropped
+    /// rather than merged with other groups
+    fn nested(&mut self, name: &std::path::Path, (entries, directories): Members) {
+        self.directory(name.to_path_buf());
+        for mut entry in entries {
+            entry.path = name.join(&entry.path);
+            entry.link = None;
+            self.file(entry);
+        }
+        self.directories
+            .extend(directories.into_iter().map(|path| name.join(path)));
+    }
+
+    fn parents(&mut self, name: &std::path::Path) {
+        self.directories.extend(
+            name.ancestors()
+                .skip(1)
+                .filter(|a| !a.as_os_str().is_empty())
+                .map(std::path::Path::to_path_buf),
+        );
+    }
+
+    /// Resolves the links into entries, sorted as the crawler sorts them
+    fn finish(self, path: &std::path::Path) -> Members {
+        let mut links = std::collections::HashMap::<_, Vec<_>>::new();
+        for (name, target) in &self.hardlinks {
+            match target {
+                Some(target) if self.files.contains_key(target) => {
+                    links.entry(target.clone()).or_default().push(name.clone());
+                }
+                _ => log::warn!(
+                    "Skipping hardlink `{}` in {}, its target is not in the archive",
+                    name.display(),
+                    path.display()
+                ),
+            }
+        }
+
+        let mut resolved = Vec::new();
+        for (name, target) in &self.symlinks {
+            match target.as_ref().and_then(|target| self.resolve(target)) {
+                Some(target) => {
+                    let mut linked = target.linked(name.clone());
+                    linked.kind = entry::Kind::Symlink;
+                    linked.link = None;
+                    resolved.push(linked);
+                }
+                None => log::warn!(
+                    "Skipping symlink `{}` in {}, it does not point to a file in the archive",
+                    name.display(),
+                    path.display()
+                ),
+            }
+        }
+
+        let mut entries = self.files.into_values().chain(resolved).collect::<Vec<_>>();
+        entries.sort_unstable();
+        crawler::resolve_links(&mut entries, links);
+
+        (entries, self.directories.into_iter().collect())
+    }
+
+    /// Follows a chain of symlinks to the file at its end
+    fn resolve<'a>(&'a self, mut target: &'a std::path::Path) -> Option<&'a entry::Entry> {
+        // As many hops as the kernel follows before giving up with `ELOOP`
+        for _ in 0..40 {
+            if let Some(file) = self.files.get(target) {
+                return Some(file);
+            }
+            target = self.symlinks.get(target)?.as_deref()?;
+        }
+        None
+    }
+}
+
+/// Makes a member path relative to the root of the archive, or `None` if it points outside of it
+fn relative(path: &std::path::Path) -> Option<std::path::PathBuf> {
+    let mut relative = std::path::PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::Normal(name) => relative.push(name),
+            std::path::Component::ParentDir => {
+                if !relative.pop() {
+                    return None;
+                }
+            }
+            std::path::Component::CurDir
+            | std::path::Component::RootDir
+            | std::path::Component::Prefix(_) => {}
+        }
+    }
+    Some(relative)
+}
+
+/// Reads the extended attributes stored as `SCHILY.xattr` PAX records, as GNU and BSD tar do
+fn attributes<R: std::io::Read>(
+    member: &mut tar::Entry<'_, R>,
+) -> std::io::Result<entry::Attributes> {
+    let Some(extensions) = member.pax_extensions()? else {
+        return Ok(entry::Attributes::new(Vec::new()));
+    };
+
+    let mut attributes = Vec::new();
+    for extension in extensions {
+        let extension = extension?;
+        if let Ok(key) = extension.key()
+            && let Some(name) = key.strip_prefix("SCHILY.xattr.")
+        {
+            attributes.push((
+                std::ffi::OsString::from(name),
+                extension.value_bytes().to_vec(),
+            ));
+        }
+    }
+    Ok(entry::Attributes::new(attributes))
+}
+
+/// The modification time from the extended timestamp field if there is one, or else from the
+/// MS-DOS timestamp, which has no time zone and is taken as UTC
+fn modified<R: std::io::Read>(member: &zip::read::ZipFile<'_, R>) -> Option<std::time::SystemTime> {
+    let extended = member.extra_data_fields().find_map(|field| match field {
+        zip::extra_fields::ExtraField::ExtendedTimestamp(timestamp) => timestamp.mod_time(),
+        zip::extra_fields::ExtraField::Ntfs(_) => None,
+    });
+    if let Some(secs) = extended {
+        return Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(u64::from(secs)));
+    }
+
+    let dos = member.last_modified()?;
+    let date = time::Date::from_calendar_date(
+        i32::from(dos.year()),
+        time::Month::try_from(dos.month()).ok()?,
+        dos.day(),
+    )
+    .ok()?;
+    let time = time::Time::from_hms(dos.hour(), dos.minute(), dos.second()).ok()?;
+    Some(time::PrimitiveDateTime::new(date, time).assume_utc().into())
+}
@@ -10,6 +10,16 @@ enum Error {
     Create(std::io::Error),
     #[error("Could not open file: {0}")]
     Open(std::io::Error),
+    #[error("Invalid glob: {0}")]
+    Glob(#[from] glob::PatternError),
+    #[error("Unknown normalization rule `{0}`, expected `eol`, `whitespace` or `bom`")]
+    Rule(String),
+    #[error("Expected `GLOB=CANONICALIZER`")]
+    Canonicalization,
+    #[error("Expected a revision after `@`")]
+    Revision,
+    #[error("Unknown compression `{0}`, expected `gzip`, `zstd` or `xz`")]
+    Compression(String),
 }
 
 pub fn parse() -> Option<Command> {
@@ -25,16 +35,32 @@ struct App {
 #[derive(Debug, clap::Subcommand)]
 pub enum Command {
     Scan(Scan),
+    Index(Index),
     Print(Print),
     Copy(Copy),
+    Merge(Merge),
+    Diff(Diff),
+    Dupes(Dupes),
+    Dedup(Dedup),
+    Verify(Verify),
+    Export(Export),
+    Agent(Agent),
 }
 
 impl Command {
     pub fn verbosity(&self) -> log::LevelFilter {
         match self {
             Command::Scan(Scan { verbosity, .. })
+            | Command::Index(Index { verbosity, .. })
             | Command::Print(Print { verbosity, .. })
-            | Command::Copy(Copy { verbosity, .. }) => to_verbosity(*verbosity),
+            | Command::Copy(Copy { verbosity, .. })
+            | Command::Merge(Merge { verbosity, .. })
+            | Command::Diff(Diff { verbosity, .. })
+            | Command::Dupes(Dupes { verbosity, .. })
+            | Command::Dedup(Dedup { verbosity, .. })
+            | Command::Verify(Verify { verbosity, .. })
+            | Command::Export(Export { verbosity, .. })
+            | Command::Agent(Agent { verbosity, .. }) => to_verbosity(*verbosity),
         }
     }
 }
@@ -47,18 +73,46 @@ pub struct Scan {
     /// Show matched items
     #[arg(short, long, default_value = "diff")]
     pub print: PrintFilter,
-    /// Path to the `left` directory to compare
-    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
-    pub left: std::path::PathBuf,
-    /// Path to the `right` directory to compare
-    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
-    pub right: std::path::PathBuf,
+    /// Path to the `left` directory, archive, manifest generated by the `index` command, checksum
+    /// listing such as `SHA256SUMS`, or mtree specification, or `git:REPOSITORY[@REVISION]`, or
+    /// `cmd:COMMAND` running `dircmp agent`, to compare
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_side))]
+    pub left: Side,
+    /// Path to the `right` directory, archive, manifest generated by the `index` command, checksum
+    /// listing such as `SHA256SUMS`, or mtree specification, or `git:REPOSITORY[@REVISION]`, or
+    /// `cmd:COMMAND` running `dircmp agent`, to compare
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_side))]
+    pub right: Side,
+    /// More sides to compare along, laying out all of them as a matrix
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_side))]
+    pub more: Vec<Side>,
+    /// Lay out which sides hold each path and which agree on its content as a matrix, even with
+    /// only two sides
+    #[arg(long)]
+    pub matrix: bool,
     /// Path to write results to
-    #[arg(short, long, required_if_eq("print", "none"), value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_write_file))]
-    pub output: Option<std::sync::Arc<std::fs::File>>,
+    #[arg(short, long, required_if_eq("print", "none"))]
+    pub output: Option<std::path::PathBuf>,
     /// Path to write the TSV summary to
     #[arg(short, long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_write_file))]
     pub summary: Option<std::sync::Arc<std::fs::File>>,
+    #[command(flatten)]
+    pub options: Options,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Index {
+    /// Verbosity level
+    #[arg(short, global = true, action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+    /// Path to the directory to index
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
+    pub path: std::path::PathBuf,
+    /// Path to write the manifest to
+    #[arg(short, long)]
+    pub output: std::path::PathBuf,
+    #[command(flatten)]
+    pub options: Options,
 }
 
 #[derive(Debug, clap::Args)]
@@ -69,7 +123,17 @@ pub struct Print {
     /// Show matched items
     #[arg(short, long)]
     pub matched: bool,
-    /// Path to the results file generated by the `scan` command
+    /// List modified files first, least similar first (requires `scan --similarity`)
+    #[arg(long)]
+    pub sort_similarity: bool,
+    /// Hide modified files less similar than this percentage
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub min_similarity: Option<u8>,
+    /// Hide modified files more similar than this percentage
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub max_similarity: Option<u8>,
+    /// Path to the results file generated by the `scan` command, either two linked sides or a
+    /// matrix
     #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_read_file))]
     pub input: std::sync::Arc<std::fs::File>,
     /// Path to write the TSV summary to
@@ -91,6 +155,334 @@ pub struct Copy {
     /// Path to the `right` directory to compare
     #[arg(short, long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
     pub target: std::path::PathBuf,
+    #[command(flatten)]
+    pub options: Options,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Merge {
+    /// Verbosity level
+    #[arg(short, global = true, action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+    /// Show unchanged items
+    #[arg(short, long, default_value = "diff")]
+    pub print: PrintFilter,
+    /// Path to the `base` directory both sides changed from, or any other side `scan` accepts
+    #[arg(short, long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_side))]
+    pub base: Side,
+    /// Path to the `left` directory, or any other side `scan` accepts, to merge
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_side))]
+    pub left: Side,
+    /// Path to the `right` directory, or any other side `scan` accepts, to merge
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_side))]
+    pub right: Side,
+    /// Path to write the TSV summary to
+    #[arg(short, long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_write_file))]
+    pub summary: Option<std::sync::Arc<std::fs::File>>,
+    /// Path to the directory to write the merge into, empty or holding a copy of `base`
+    ///
+    /// Both `left` and `right` must be directories. Conflicting versions are written with a
+    /// `.left` or `.right` suffix, and listed in `.dircmp-conflicts`
+    #[arg(short, long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
+    pub target: Option<std::path::PathBuf>,
+    /// Path to write the TSV list of conflicts to
+    #[arg(long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_write_file))]
+    pub conflicts: Option<std::sync::Arc<std::fs::File>>,
+    #[command(flatten)]
+    pub options: Options,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Diff {
+    /// Verbosity level
+    #[arg(short, global = true, action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+    /// Path to the results file generated by the `scan` command
+    #[arg(long, conflicts_with_all = ["left", "right"], value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_read_file))]
+    pub input: Option<std::sync::Arc<std::fs::File>>,
+    /// Path to the `left` directory to compare
+    #[arg(required_unless_present = "input", value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
+    pub left: Option<std::path::PathBuf>,
+    /// Path to the `right` directory to compare
+    #[arg(required_unless_present = "input", value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
+    pub right: Option<std::path::PathBuf>,
+    /// Largest file size, in bytes, that is diffed as text
+    #[arg(short, long, default_value_t = 1024 * 1024)]
+    pub limit: u64,
+    #[command(flatten)]
+    pub options: Options,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Dupes {
+    /// Verbosity level
+    #[arg(short, global = true, action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+    /// Path to the directory to search for duplicates
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
+    pub path: std::path::PathBuf,
+    /// Path to write the TSV summary to
+    #[arg(short, long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_write_file))]
+    pub summary: Option<std::sync::Arc<std::fs::File>>,
+    #[command(flatten)]
+    pub options: Hashing,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Dedup {
+    /// Verbosity level
+    #[arg(short, global = true, action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+    /// Path to the directory to deduplicate
+    #[arg(required_unless_present = "undo", value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
+    pub path: Option<std::path::PathBuf>,
+    /// How duplicates are replaced, reflinks fall back to hardlinks where unsupported
+    #[arg(short, long, default_value = "reflink")]
+    pub method: Method,
+    /// Only show what would be replaced
+    #[arg(long, conflicts_with_all = ["journal", "undo"])]
+    pub dry_run: bool,
+    /// Path to the journal recording every replacement, needed to undo them
+    #[arg(short, long, required_unless_present = "dry_run")]
+    pub journal: Option<std::path::PathBuf>,
+    /// Undo the replacements recorded in the journal instead
+    #[arg(long, requires = "journal", conflicts_with = "path")]
+    pub undo: bool,
+    #[command(flatten)]
+    pub options: Hashing,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Verify {
+    /// Verbosity level
+    #[arg(short, global = true, action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+    /// Path to the directory to verify
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
+    pub path: std::path::PathBuf,
+    /// Path to the manifest generated by the `index` command
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_read_file))]
+    pub manifest: std::sync::Arc<std::fs::File>,
+    /// Path to write the TSV summary to
+    #[arg(short, long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_write_file))]
+    pub summary: Option<std::sync::Arc<std::fs::File>>,
+    #[command(flatten)]
+    pub options: Options,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Export {
+    /// Verbosity level
+    #[arg(short, global = true, action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+    /// Path to the directory, archive, manifest generated by the `index` command, checksum listing,
+    /// mtree specification, `git:REPOSITORY[@REVISION]`, or `cmd:COMMAND` running `dircmp agent`,
+    /// to export
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_side))]
+    pub side: Side,
+    /// Format to export to
+    #[arg(short, long, default_value = "sums")]
+    pub format: Format,
+    /// Path to write to, defaults to stdout
+    #[arg(short, long, value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), to_write_file))]
+    pub output: Option<std::sync::Arc<std::fs::File>>,
+    #[command(flatten)]
+    pub options: Options,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Agent {
+    /// Verbosity level
+    #[arg(short, global = true, action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+    /// Path to the directory to serve on stdin and stdout, for a `cmd:COMMAND` side to compare
+    #[arg(value_parser = clap::builder::TypedValueParser::try_map(clap::builder::OsStringValueParser::new(), parse_dir))]
+    pub path: std::path::PathBuf,
+    #[command(flatten)]
+    pub options: Options,
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, clap::Args)]
+pub struct Options {
+    #[command(flatten)]
+    pub hashing: Hashing,
+    /// Compare extended attributes and POSIX ACLs (reproduced in the target when copying)
+    #[arg(short = 'x', long)]
+    pub attributes: bool,
+    /// Compare the mode, user and group of files
+    #[arg(long)]
+    pub permissions: bool,
+    /// Report modified files that were only appended to or truncated
+    #[arg(long)]
+    pub prefixes: bool,
+    /// Score how similar the contents of modified files are
+    #[arg(long)]
+    pub similarity: bool,
+    /// Match paths that differ only by Unicode normalization (NFC/NFD)
+    #[arg(short, long)]
+    pub unicode: bool,
+    /// Match paths that differ only by case
+    #[arg(short, long)]
+    pub ignore_case: bool,
+    /// Compare the members of archives found in the trees instead of the archives themselves
+    #[arg(long)]
+    pub into_archives: bool,
+}
+
+// The `Options` that only affect how file contents are hashed, all that matters to the
+// commands looking within a single tree
+#[derive(Debug, clap::Args)]
+pub struct Hashing {
+    /// Digest to hash file contents with, defaults to the one of the loaded checksum listing,
+    /// mtree specification or manifest, if any, and to MD5 otherwise
+    #[arg(long)]
+    pub digest: Option<Digest>,
+    /// Normalize text files matching GLOB before hashing
+    ///
+    /// The rules to apply may be restricted with a comma separated list of `eol`, `whitespace`
+    /// and `bom` after a colon, e.g. `*.conf:eol,bom`. All rules are applied by default
+    #[arg(short, long, value_name = "GLOB[:RULES]", value_parser = parse_normalization)]
+    pub normalize: Vec<dircmp::Normalization>,
+    /// Hash the canonical form of files matching GLOB instead of their content
+    ///
+    /// CANONICALIZER is either `json` for the built-in JSON canonicalizer, or a shell command
+    /// that reads the content from stdin and writes the canonical form to stdout
+    #[arg(short, long, value_name = "GLOB=CANONICALIZER", value_parser = parse_canonicalization)]
+    pub canonicalize: Vec<dircmp::Canonicalization>,
+    /// Hash the decompressed content of compressed files, matching `foo.log.gz` with `foo.log`
+    ///
+    /// Applies to `.gz`, `.zst` and `.xz` files by default, or to the given comma separated
+    /// extensions, whose FORMAT of `gzip`, `zstd` or `xz` is guessed unless given, e.g.
+    /// `--decompress=gz,dz=gzip`
+    #[arg(short = 'z', long, value_name = "EXT[=FORMAT]", num_args = 0..=1, require_equals = true, value_delimiter = ',', value_parser = parse_decompression)]
+    pub decompress: Option<Vec<dircmp::Decompression>>,
+    /// Reuse the hashes of unchanged files from the hash cache at PATH, and save the new ones
+    ///
+    /// PATH defaults to `dircmp/hashes` in the user cache directory
+    #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true)]
+    #[allow(clippy::option_option)]
+    pub cache: Option<Option<std::path::PathBuf>>,
+    /// Do not read nor update the hash cache, overriding an earlier `--cache`
+    #[arg(long, overrides_with = "cache")]
+    pub no_cache: bool,
+    /// Hash every file again, refreshing the hash cache
+    #[arg(long, requires = "cache")]
+    pub rehash: bool,
+}
+
+impl From<Options> for dircmp::Options {
+    fn from(
+        Options {
+            hashing,
+            attributes,
+            permissions,
+            prefixes,
+            similarity,
+            unicode,
+            ignore_case,
+            into_archives,
+        }: Options,
+    ) -> Self {
+        Self {
+            attributes,
+            permissions,
+            prefixes,
+            similarity,
+            unicode,
+            ignore_case,
+            into_archives,
+            ..Self::from(hashing)
+        }
+    }
+}
+
+impl From<Hashing> for dircmp::Options {
+    fn from(
+        Hashing {
+            digest,
+            normalize,
+            canonicalize,
+            decompress,
+            cache,
+            no_cache,
+            rehash,
+        }: Hashing,
+    ) -> Self {
+        Self {
+            algorithm: digest.map(dircmp::Algorithm::from).unwrap_or_default(),
+            normalizations: normalize,
+            canonicalizations: canonicalize,
+            decompressions: decompress
+                .map(|decompressions| {
+                    if decompressions.is_empty() {
+                        dircmp::Decompression::defaults()
+                    } else {
+                        decompressions
+                    }
+                })
+                .unwrap_or_default(),
+            cache: cache
+                .filter(|_| !no_cache)
+                .and_then(|cache| cache.or_else(default_cache)),
+            rehash,
+            ..Self::default()
+        }
+    }
+}
+
+fn default_cache() -> Option<std::path::PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|path| !path.is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".cache")))
+        .map(|path| path.join("dircmp").join("hashes"))
+}
+
+/// One side of a comparison
+#[derive(Debug, Clone)]
+pub enum Side {
+    Directory(std::path::PathBuf),
+    /// A tar or zip archive, indexed like a directory
+    Archive(std::path::PathBuf),
+    Manifest(std::sync::Arc<std::fs::File>),
+    /// A coreutils-style checksum listing, describing the directory it is in
+    Checksums(std::path::PathBuf, std::sync::Arc<std::fs::File>),
+    /// A BSD mtree specification, describing the directory it is in
+    Mtree(std::path::PathBuf, std::sync::Arc<std::fs::File>),
+    /// A revision of a git repository, read from its object database
+    Git(std::path::PathBuf, String),
+    /// A shell command starting `dircmp agent`, e.g. over ssh
+    Command(String),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, clap::ValueEnum)]
+pub enum Digest {
+    Md5,
+    Sha256,
+}
+
+impl From<Digest> for dircmp::Algorithm {
+    fn from(digest: Digest) -> Self {
+        match digest {
+            Digest::Md5 => Self::Md5,
+            Digest::Sha256 => Self::Sha256,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, clap::ValueEnum)]
+pub enum Format {
+    /// `md5sum`/`sha256sum` listing, depending on the digest of the side
+    Sums,
+    /// BSD mtree specification
+    Mtree,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, clap::ValueEnum)]
+pub enum Method {
+    Hardlink,
+    Reflink,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, clap::ValueEnum)]
@@ -112,6 +504,147 @@ fn parse_dir(input: std::ffi::OsString) -> Result<std::path::PathBuf, Error> {
     }
 }
 
+fn parse_side(input: std::ffi::OsString) -> Result<Side, Error> {
+    if let Some(spec) = input.to_str().and_then(|input| input.strip_prefix("git:")) {
+        return parse_git(spec);
+    }
+    if let Some(command) = input.to_str().and_then(|input| input.strip_prefix("cmd:")) {
+        return Ok(Side::Command(String::from(command)));
+    }
+
+    let path = std::path::Path::new(&input);
+    if dircmp::is_archive(path) {
+        std::fs::canonicalize(path)
+            .map(Side::Archive)
+            .map_err(|_| Error::BadPath)
+    } else if path.is_file() && is_checksums(path) {
+        let path = std::fs::canonicalize(path).map_err(|_| Error::BadPath)?;
+        to_read_file(input).map(|file| Side::Checksums(path, file))
+    } else if path.is_file() && is_mtree(path) {
+        let path = std::fs::canonicalize(path).map_err(|_| Error::BadPath)?;
+        to_read_file(input).map(|file| Side::Mtree(path, file))
+    } else if path.is_file() {
+        to_read_file(input).map(Side::Manifest)
+    } else {
+        parse_dir(input).map(Side::Directory)
+    }
+}
+
+/// Parses `REPOSITORY[@REVISION]`, the revision defaulting to `HEAD`
+///
+/// Both paths and revisions may contain `@`, so the repository is the shortest prefix that is an
+/// existing directory
+fn parse_git(spec: &str) -> Result<Side, Error> {
+    let (path, revision) = spec
+        .match_indices('@')
+        .map(|(at, _)| (&spec[..at], &spec[at + 1..]))
+        .find(|(path, _)| std::path::Path::new(path).is_dir())
+        .unwrap_or((spec, "HEAD"));
+    if revision.is_empty() {
+        return Err(Error::Revision);
+    }
+
+    let path = parse_dir(std::ffi::OsString::from(path))?;
+    Ok(Side::Git(path, String::from(revision)))
+}
+
+/// Recognizes checksum listings by their conventional names, `SHA256SUMS`, `MD5SUMS` or
+/// `*.sha256`, `*.md5` and the like
+fn is_checksums(path: &std::path::Path) -> bool {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    let extension = path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    name.ends_with("sums") || ["md5", "sha256", "md5sum", "sha256sum"].contains(&extension.as_str())
+}
+
+/// Recognizes mtree specifications by their `.mtree` extension or `#mtree` signature
+fn is_mtree(path: &std::path::Path) -> bool {
+    use std::io::Read;
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mtree"))
+    {
+        return true;
+    }
+
+    let mut signature = [0; 6];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut signature))
+        .is_ok_and(|()| &signature == b"#mtree")
+}
+
+fn parse_decompression(input: &str) -> Result<dircmp::Decompression, Error> {
+    let (extension, format) = match input.split_once('=') {
+        Some((extension, format)) => (extension, format),
+        None => (input, input.rsplit('.').next().unwrap_or(input)),
+    };
+    let extension = extension.trim_start_matches('.');
+    if extension.is_empty() {
+        return Err(Error::Compression(String::from(input)));
+    }
+
+    dircmp::Compression::from_extension(format)
+        .map(|format| dircmp::Decompression {
+            extension: String::from(extension),
+            format,
+        })
+        .ok_or_else(|| Error::Compression(String::from(format)))
+}
+
+fn parse_normalization(input: &str) -> Result<dircmp::Normalization, Error> {
+    let Some((glob, rules)) = input.rsplit_once(':') else {
+        return Ok(dircmp::Normalization {
+            glob: glob::Pattern::new(input)?,
+            rules: dircmp::Rules::default(),
+        });
+    };
+
+    let mut parsed = dircmp::Rules {
+        line_endings: false,
+        trailing_whitespace: false,
+        bom: false,
+    };
+
+    for rule in rules.split(',') {
+        match rule {
+            "eol" => parsed.line_endings = true,
+            "whitespace" => parsed.trailing_whitespace = true,
+            "bom" => parsed.bom = true,
+            rule => return Err(Error::Rule(String::from(rule))),
+        }
+    }
+
+    Ok(dircmp::Normalization {
+        glob: glob::Pattern::new(glob)?,
+        rules: parsed,
+    })
+}
+
+fn parse_canonicalization(input: &str) -> Result<dircmp::Canonicalization, Error> {
+    let Some((glob, canonicalizer)) = input.split_once('=') else {
+        return Err(Error::Canonicalization);
+    };
+
+    let canonicalizer: std::sync::Arc<dyn dircmp::Canonicalizer> = match canonicalizer {
+        "" => return Err(Error::Canonicalization),
+        "json" => std::sync::Arc::new(dircmp::Json),
+        command => std::sync::Arc::new(dircmp::Command::new("sh", ["-c", command])),
+    };
+
+    Ok(dircmp::Canonicalization {
+        glob: glob::Pattern::new(glob)?,
+        canonicalizer,
+    })
+}
+
 fn to_write_file(input: std::ffi::OsString) -> Result<std::sync::Arc<std::fs::File>, Error> {
     std::fs::File::create(input)
         .map_err(Error::Create)
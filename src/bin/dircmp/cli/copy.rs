@@ -6,19 +6,41 @@ pub enum Error {
     CreateDir(std::path::PathBuf, std::io::Error),
     #[error("Could not copy file `{0}`: {1}")]
     CopyFile(std::path::PathBuf, std::io::Error),
+    #[error("Could not set extended attribute `{1}` on `{0}`: {2}")]
+    SetAttribute(std::path::PathBuf, String, std::io::Error),
+    #[error("Could not remove file `{0}`: {1}")]
+    Remove(std::path::PathBuf, std::io::Error),
+    #[error("Could not write conflict list `{0}`: {1}")]
+    Conflicts(std::path::PathBuf, std::io::Error),
 }
 
+/// Name of the list of conflicts written into the target of a merge
+const CONFLICTS: &str = ".dircmp-conflicts";
+
+/// A conflicting path with the files its `.left` and `.right` versions were written to
+type Conflicted<'a> = (
+    &'a std::path::Path,
+    dircmp::Conflict,
+    Option<std::path::PathBuf>,
+    Option<std::path::PathBuf>,
+);
+
 pub fn copy(
     reference: dircmp::Directory,
     derived: dircmp::Directory,
     target: &std::path::Path,
+    options: &dircmp::Options,
 ) -> Result<usize, Error> {
-    let reference = copy_reference(reference, target)?;
-    let derived = copy_derived(derived, target)?;
+    let reference = copy_reference(reference, target, options)?;
+    let derived = copy_derived(derived, target, options)?;
     Ok(reference + derived)
 }
 
-fn copy_reference(reference: dircmp::Directory, target: &std::path::Path) -> Result<usize, Error> {
+fn copy_reference(
+    reference: dircmp::Directory,
+    target: &std::path::Path,
+    options: &dircmp::Options,
+) -> Result<usize, Error> {
     let mut count = 0;
     let (path, entries) = reference.decompose();
 
@@ -26,14 +48,24 @@ fn copy_reference(reference: dircmp::Directory, target: &std::path::Path) -> Res
         .into_iter()
         .filter(|e| matches!(e.status(), dircmp::Status::Same(_)))
     {
-        copy_file("unconflicting", &path, target, entry.path())?;
+        copy_file(
+            "unconflicting",
+            &path,
+            &entry,
+            &target.join(entry.path()),
+            options,
+        )?;
         count += 1;
     }
 
     Ok(count)
 }
 
-fn copy_derived(derived: dircmp::Directory, target: &std::path::Path) -> Result<usize, Error> {
+fn copy_derived(
+    derived: dircmp::Directory,
+    target: &std::path::Path,
+    options: &dircmp::Options,
+) -> Result<usize, Error> {
     let mut count = 0;
     let (path, entries) = derived.decompose();
 
@@ -45,20 +77,146 @@ fn copy_derived(derived: dircmp::Directory, target: &std::path::Path) -> Result<
             _ => continue,
         };
 
-        copy_file(status, &path, target, entry.path())?;
+        copy_file(status, &path, &entry, &target.join(entry.path()), options)?;
         count += 1;
     }
 
     Ok(count)
 }
 
+/// Writes the merge of `left` and `right` into `target`, which is expected to be empty or to hold
+/// a copy of `base`, returning how many files were written and how many conflicted
+///
+/// Files moved away or deleted by either side are removed from `target` if it holds them, and a
+/// file moved by one side and edited by the other is written at its new path with the edits.
+/// Conflicting versions are written next to their path with a `.left` or `.right` suffix, and
+/// listed with them in a `.dircmp-conflicts` file
+pub fn merge(
+    merge: &dircmp::Merge,
+    target: &std::path::Path,
+    options: &dircmp::Options,
+) -> Result<(usize, usize), Error> {
+    for change in merge.changes() {
+        let (Some(base), left, right) = merge.entries(change) else {
+            continue;
+        };
+        let kept = match change.outcome() {
+            dircmp::Outcome::Left | dircmp::Outcome::Both => left,
+            dircmp::Outcome::Right => right,
+            // Moved by one of the sides, so never kept at its path
+            dircmp::Outcome::Combined => None,
+            dircmp::Outcome::Unchanged | dircmp::Outcome::Conflict(_) => continue,
+        };
+        if kept.is_none_or(|kept| kept.path() != base.path()) {
+            remove_file(&target.join(base.path()))?;
+        }
+    }
+
+    let mut count = 0;
+    let mut conflicts = Vec::new();
+    for change in merge.changes() {
+        let (base, left, right) = merge.entries(change);
+        let (status, source, entry) = match change.outcome() {
+            dircmp::Outcome::Unchanged => ("unconflicting", merge.left(), left),
+            dircmp::Outcome::Left => ("left", merge.left(), left),
+            dircmp::Outcome::Right => ("right", merge.right(), right),
+            dircmp::Outcome::Both => ("merged", merge.left(), left),
+            dircmp::Outcome::Combined => {
+                let (Some(base), Some(left), Some(right)) = (base, left, right) else {
+                    continue;
+                };
+                // The content of the side that kept the path, at the path of the other
+                let (source, edited, moved) = if left.path() == base.path() {
+                    (merge.left(), left, right)
+                } else {
+                    (merge.right(), right, left)
+                };
+                let target = target.join(moved.path());
+                copy_file("combined", source.path(), edited, &target, options)?;
+                count += 1;
+                continue;
+            }
+            dircmp::Outcome::Conflict(conflict) => {
+                let left_path = left.map(|entry| suffixed(entry.path(), "left"));
+                let right_path = right.map(|entry| suffixed(entry.path(), "right"));
+                for (source, entry, path) in [
+                    (merge.left(), left, &left_path),
+                    (merge.right(), right, &right_path),
+                ] {
+                    if let (Some(entry), Some(path)) = (entry, path) {
+                        let target = target.join(path);
+                        copy_file("conflicting", source.path(), entry, &target, options)?;
+                        count += 1;
+                    }
+                }
+                if let Some(entry) = base.or(left).or(right) {
+                    conflicts.push((entry.path(), conflict, left_path, right_path));
+                }
+                continue;
+            }
+        };
+
+        if let Some(entry) = entry {
+            let target = target.join(entry.path());
+            copy_file(status, source.path(), entry, &target, options)?;
+            count += 1;
+        }
+    }
+
+    if !conflicts.is_empty() {
+        let path = target.join(CONFLICTS);
+        write_conflicts(&path, &conflicts).map_err(|err| Error::Conflicts(path, err))?;
+    }
+
+    Ok((count, conflicts.len()))
+}
+
+/// Appends `.suffix` to the name of `path`
+fn suffixed(path: &std::path::Path, suffix: &str) -> std::path::PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    std::path::PathBuf::from(name)
+}
+
+fn remove_file(path: &std::path::Path) -> Result<(), Error> {
+    match std::fs::remove_file(path) {
+        Ok(()) => {
+            log::info!("Removed file `{}`", path.display());
+            Ok(())
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(Error::Remove(path.to_path_buf(), err)),
+    }
+}
+
+fn write_conflicts(path: &std::path::Path, conflicts: &[Conflicted]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let display = |path: &Option<std::path::PathBuf>| {
+        path.as_ref()
+            .map_or_else(String::new, |path| path.display().to_string())
+    };
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for (path, conflict, left, right) in conflicts {
+        writeln!(
+            out,
+            "{path}\t{conflict}\t{left}\t{right}",
+            path = path.display(),
+            left = display(left),
+            right = display(right),
+        )?;
+    }
+    out.flush()
+}
+
 fn copy_file(
     status: &'static str,
     src: &std::path::Path,
-    dst: &std::path::Path,
-    path: &std::path::Path,
+    entry: &dircmp::Entry,
+    target: &std::path::Path,
+    options: &dircmp::Options,
 ) -> Result<(), Error> {
-    let target = dst.join(path);
     log::info!("Copying {status} file `{}`", target.display());
 
     if let Some(dir) = target.parent() {
@@ -66,11 +224,25 @@ fn copy_file(
             return Err(Error::CreateDir(std::path::PathBuf::from(dir), err));
         }
     } else {
-        return Err(Error::NoParent(target));
+        return Err(Error::NoParent(target.to_path_buf()));
     }
 
-    if let Err(err) = std::fs::copy(src.join(path), &target) {
-        return Err(Error::CopyFile(target, err));
+    if let Err(err) = std::fs::copy(src.join(entry.path()), target) {
+        return Err(Error::CopyFile(target.to_path_buf(), err));
+    }
+
+    if options.attributes
+        && let Some(attributes) = entry.attributes()
+    {
+        for (name, value) in attributes.iter() {
+            if let Err(err) = xattr::set(target, name, value) {
+                return Err(Error::SetAttribute(
+                    target.to_path_buf(),
+                    name.to_string_lossy().into_owned(),
+                    err,
+                ));
+            }
+        }
     }
 
     Ok(())
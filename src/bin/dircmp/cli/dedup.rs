@@ -0,0 +1,423 @@
+use super::args;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Dircmp(#[from] dircmp::Error),
+    #[error("Could not read metadata of `{0}`: {1}")]
+    Metadata(std::path::PathBuf, std::io::Error),
+    #[error("Could not replace `{0}`: {1}")]
+    Replace(std::path::PathBuf, std::io::Error),
+    #[error("Could not restore `{0}`: {1}")]
+    Restore(std::path::PathBuf, std::io::Error),
+    #[error("Could not open journal `{0}`: {1}")]
+    OpenJournal(std::path::PathBuf, std::io::Error),
+    #[error("Could not write to journal: {0}")]
+    WriteJournal(std::io::Error),
+    #[error("Could not read journal: {0}")]
+    ReadJournal(std::io::Error),
+    #[error("Invalid journal record: {0}")]
+    Record(serde_json::Error),
+    #[error("Could not write to stdout: {0}")]
+    Print(std::io::Error),
+}
+
+/// A replaced file, with what is needed to give it back its own copy and metadata
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Record {
+    kept: std::path::PathBuf,
+    replaced: std::path::PathBuf,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    modified: Timestamp,
+}
+
+/// A time as seconds and nanoseconds since the epoch, signed so that earlier times are kept too
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+struct Timestamp {
+    seconds: i64,
+    nanos: u32,
+}
+
+impl From<std::time::SystemTime> for Timestamp {
+    fn from(time: std::time::SystemTime) -> Self {
+        match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(after) => Self {
+                seconds: after.as_secs().cast_signed(),
+                nanos: after.subsec_nanos(),
+            },
+            // Nanoseconds always count forward, from the second before
+            Err(before) => {
+                let before = before.duration();
+                match before.subsec_nanos() {
+                    0 => Self {
+                        seconds: -before.as_secs().cast_signed(),
+                        nanos: 0,
+                    },
+                    nanos => Self {
+                        seconds: -before.as_secs().cast_signed() - 1,
+                        nanos: 1_000_000_000 - nanos,
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl From<Timestamp> for std::time::SystemTime {
+    fn from(Timestamp { seconds, nanos }: Timestamp) -> Self {
+        let whole = std::time::Duration::from_secs(seconds.unsigned_abs());
+        let second = if seconds < 0 {
+            std::time::UNIX_EPOCH - whole
+        } else {
+            std::time::UNIX_EPOCH + whole
+        };
+        second + std::time::Duration::from_nanos(nanos.into())
+    }
+}
+
+/// Replaces every copy but the first of each group with a link to it, returning how many files
+/// were replaced and how many bytes were reclaimed
+///
+/// Copies are compared byte-for-byte before being replaced, and every replacement is appended to
+/// the `journal` before it is done so an interrupted run can still be undone. Without a journal
+/// nothing is replaced, which is how dry runs are made
+pub fn dedup(
+    base: &std::path::Path,
+    groups: &[dircmp::Duplicates],
+    method: args::Method,
+    journal: Option<&std::path::Path>,
+) -> Result<(usize, u64), Error> {
+    use std::io::Write;
+
+    let mut journal = journal
+        .map(|path| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| Error::OpenJournal(path.to_path_buf(), e))
+        })
+        .transpose()?;
+    let mut out = std::io::stdout().lock();
+
+    let mut count = 0;
+    let mut reclaimed = 0;
+    for group in groups {
+        let Some((first, rest)) = group.paths().split_first() else {
+            continue;
+        };
+
+        let kept = base.join(first);
+        let kept_metadata =
+            std::fs::metadata(&kept).map_err(|e| Error::Metadata(kept.clone(), e))?;
+
+        for path in rest {
+            let replaced = base.join(path);
+            let metadata = std::fs::symlink_metadata(&replaced)
+                .map_err(|e| Error::Metadata(replaced.clone(), e))?;
+
+            if same_file(&metadata, &kept_metadata) {
+                continue;
+            }
+
+            if !identical(&kept, &replaced)? {
+                log::warn!(
+                    "Skipping `{}`, its content differs from `{}`",
+                    replaced.display(),
+                    kept.display()
+                );
+                continue;
+            }
+
+            let Some(journal) = journal.as_mut() else {
+                print(&mut out, method, path, first)?;
+                count += 1;
+                reclaimed += group.size();
+                continue;
+            };
+
+            // Recorded ahead, restoring a file that ends up not being replaced is harmless
+            let (uid, gid) = owner(&metadata);
+            let record = Record {
+                kept: kept.clone(),
+                replaced: replaced.clone(),
+                mode: mode(&metadata),
+                uid,
+                gid,
+                modified: metadata
+                    .modified()
+                    .map_err(|e| Error::Metadata(replaced.clone(), e))?
+                    .into(),
+            };
+            serde_json::to_writer(&mut *journal, &record).map_err(Error::Record)?;
+            writeln!(journal).map_err(Error::WriteJournal)?;
+            journal.flush().map_err(Error::WriteJournal)?;
+
+            let Some(method) = replace(&kept, &replaced, &metadata, method)? else {
+                continue;
+            };
+            print(&mut out, method, path, first)?;
+
+            count += 1;
+            reclaimed += group.size();
+        }
+    }
+
+    Ok((count, reclaimed))
+}
+
+fn print(
+    mut out: impl std::io::Write,
+    method: args::Method,
+    path: &std::path::Path,
+    kept: &std::path::Path,
+) -> Result<(), Error> {
+    let verb = match method {
+        args::Method::Hardlink => "LINK",
+        args::Method::Reflink => "CLONE",
+    };
+    writeln!(
+        out,
+        "[33m{verb:<5}[m {} [37m-> {}[m",
+        path.display(),
+        kept.display()
+    )
+    .map_err(Error::Print)
+}
+
+/// Gives every file recorded in the `journal` its own copy and metadata back, most recent first,
+/// returning how many files were restored
+pub fn undo(journal: &std::path::Path) -> Result<usize, Error> {
+    use std::io::BufRead;
+
+    let file =
+        std::fs::File::open(journal).map_err(|e| Error::OpenJournal(journal.to_path_buf(), e))?;
+    let records = std::io::BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let line = line.map_err(Error::ReadJournal)?;
+            serde_json::from_str::<Record>(&line).map_err(Error::Record)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut count = 0;
+    for record in records.iter().rev() {
+        if !record.replaced.exists() {
+            log::warn!(
+                "Skipping `{}`, it no longer exists",
+                record.replaced.display()
+            );
+            continue;
+        }
+
+        log::info!("Restoring `{}`", record.replaced.display());
+        restore(record).map_err(|e| Error::Restore(record.replaced.clone(), e))?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn identical(left: &std::path::Path, right: &std::path::Path) -> Result<bool, Error> {
+    Ok(matches!(
+        dircmp::diff(left, right, 0)?,
+        dircmp::Diff::Binary { offset: None, .. }
+    ))
+}
+
+/// Atomically replaces `replaced` with a link to `kept`, returning the method that was used or
+/// `None` if neither is supported for this pair
+fn replace(
+    kept: &std::path::Path,
+    replaced: &std::path::Path,
+    metadata: &std::fs::Metadata,
+    method: args::Method,
+) -> Result<Option<args::Method>, Error> {
+    let temporary = temporary(replaced);
+
+    let method = match method {
+        args::Method::Reflink => match clone(kept, &temporary) {
+            Ok(()) => args::Method::Reflink,
+            Err(e) if unsupported(&e) => {
+                log::warn!(
+                    "Reflinks are not supported for `{}`, falling back to a hardlink",
+                    replaced.display()
+                );
+                args::Method::Hardlink
+            }
+            Err(e) => return Err(Error::Replace(replaced.to_path_buf(), e)),
+        },
+        args::Method::Hardlink => args::Method::Hardlink,
+    };
+
+    let result = match method {
+        args::Method::Reflink => set_metadata(
+            &temporary,
+            mode(metadata),
+            owner(metadata),
+            metadata.modified(),
+        ),
+        args::Method::Hardlink => match std::fs::hard_link(kept, &temporary) {
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                log::warn!(
+                    "Skipping `{}`, it is on another device than `{}`",
+                    replaced.display(),
+                    kept.display()
+                );
+                return Ok(None);
+            }
+            result => result,
+        },
+    }
+    .and_then(|()| std::fs::rename(&temporary, replaced));
+
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(Error::Replace(replaced.to_path_buf(), e));
+    }
+
+    Ok(Some(method))
+}
+
+fn restore(record: &Record) -> std::io::Result<()> {
+    let temporary = temporary(&record.replaced);
+    let result = std::fs::copy(&record.kept, &temporary).and_then(|_| {
+        set_metadata(
+            &temporary,
+            record.mode,
+            (record.uid, record.gid),
+            Ok(record.modified.into()),
+        )
+    });
+
+    result
+        .and_then(|()| std::fs::rename(&temporary, &record.replaced))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temporary);
+        })
+}
+
+#[cfg(unix)]
+fn set_metadata(
+    path: &std::path::Path,
+    mode: u32,
+    (uid, gid): (u32, u32),
+    modified: std::io::Result<std::time::SystemTime>,
+) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = std::fs::File::options().write(true).open(path)?;
+    file.set_modified(modified?)?;
+
+    // Only privileged users can give files away, which is fine as long as the owner is kept
+    match std::os::unix::fs::fchown(&file, Some(uid), Some(gid)) {
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            log::warn!("Could not restore the owner of `{}`: {e}", path.display());
+        }
+        result => result?,
+    }
+
+    file.set_permissions(std::fs::Permissions::from_mode(mode))
+}
+
+/// Only the modification time and whether the file is read-only can be restored here
+#[cfg(not(unix))]
+fn set_metadata(
+    path: &std::path::Path,
+    mode: u32,
+    _: (u32, u32),
+    modified: std::io::Result<std::time::SystemTime>,
+) -> std::io::Result<()> {
+    let file = std::fs::File::options().write(true).open(path)?;
+    file.set_modified(modified?)?;
+
+    let mut permissions = file.metadata()?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    file.set_permissions(permissions)
+}
+
+/// Whether both are hardlinks of the same file, by their device and inode numbers
+#[cfg(unix)]
+fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &std::fs::Metadata, _: &std::fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.mode()
+}
+
+/// The permission bits of a file that is read-only or not
+#[cfg(not(unix))]
+fn mode(metadata: &std::fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+#[cfg(unix)]
+fn owner(metadata: &std::fs::Metadata) -> (u32, u32) {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.uid(), metadata.gid())
+}
+
+#[cfg(not(unix))]
+fn owner(_: &std::fs::Metadata) -> (u32, u32) {
+    (0, 0)
+}
+
+fn temporary(path: &std::path::Path) -> std::path::PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".dircmp-dedup");
+    path.with_file_name(name)
+}
+
+#[cfg(target_os = "linux")]
+fn clone(src: &std::path::Path, dst: &std::path::Path) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let source = std::fs::File::open(src)?;
+    let target = std::fs::File::create_new(dst)?;
+
+    if unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } == -1 {
+        let error = std::io::Error::last_os_error();
+        let _ = std::fs::remove_file(dst);
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clone(_: &std::path::Path, _: &std::path::Path) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+fn unsupported(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    if matches!(
+        error.raw_os_error(),
+        Some(libc::EOPNOTSUPP | libc::EINVAL | libc::ENOTTY | libc::EXDEV)
+    ) {
+        return true;
+    }
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::Unsupported | std::io::ErrorKind::CrossesDevices
+    )
+}
@@ -0,0 +1,56 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Dircmp(#[from] dircmp::Error),
+    #[error("Could not write to stdout: {0}")]
+    Print(std::io::Error),
+}
+
+pub fn diff(
+    (left, right): &(dircmp::Directory, dircmp::Directory),
+    limit: u64,
+) -> Result<usize, Error> {
+    use std::io::Write;
+
+    let mut count = 0;
+    let mut out = std::io::stdout().lock();
+
+    for entry in left.entries() {
+        let (dircmp::Status::Modified(i, _)
+        | dircmp::Status::Appended(i, _)
+        | dircmp::Status::Truncated(i, _)) = entry.status()
+        else {
+            continue;
+        };
+
+        let left_path = left.path().join(entry.path());
+        let right_path = right
+            .path()
+            .join(unsafe { right.entries().get_unchecked(*i).path() });
+        log::debug!("Diffing `{}`", entry.path().display());
+
+        match dircmp::diff(&left_path, &right_path, limit)? {
+            dircmp::Diff::Text(diff) => write!(out, "{diff}"),
+            dircmp::Diff::Binary {
+                offset: Some(offset),
+                changed,
+            } => writeln!(
+                out,
+                "Binary files {} and {} differ at offset {offset}: {changed} bytes changed",
+                left_path.display(),
+                right_path.display(),
+            ),
+            dircmp::Diff::Binary { offset: None, .. } => writeln!(
+                out,
+                "Binary files {} and {} are identical",
+                left_path.display(),
+                right_path.display(),
+            ),
+        }
+        .map_err(Error::Print)?;
+
+        count += 1;
+    }
+
+    Ok(count)
+}
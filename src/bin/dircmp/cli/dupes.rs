@@ -0,0 +1,67 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not write to summary: {0}")]
+    Summary(std::io::Error),
+    #[error("Could not write to stdout: {0}")]
+    Print(std::io::Error),
+}
+
+pub fn to_summary(
+    output: impl std::io::Write,
+    base: &std::path::Path,
+    groups: &[dircmp::Duplicates],
+) -> Result<(), Error> {
+    let mut writer = std::io::BufWriter::new(output);
+    write_tsv(&mut writer, base, groups).map_err(Error::Summary)
+}
+
+pub fn to_stdout(base: &std::path::Path, groups: &[dircmp::Duplicates]) -> Result<(), Error> {
+    write_pretty(base, groups).map_err(Error::Print)
+}
+
+fn write_tsv(
+    mut out: impl std::io::Write,
+    base: &std::path::Path,
+    groups: &[dircmp::Duplicates],
+) -> std::io::Result<()> {
+    for group in groups {
+        write!(out, "{}\t{}", group.wasted(), group.size())?;
+        for path in group.paths() {
+            write!(out, "\t{}", base.join(path).display())?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_pretty(base: &std::path::Path, groups: &[dircmp::Duplicates]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "[37mVisiting:[m {}", base.display())?;
+    for group in groups {
+        let Some((tail, head)) = group.paths().split_last() else {
+            continue;
+        };
+        writeln!(
+            out,
+            "[33mDUPES[m {} copies of {} bytes [37m({} bytes wasted)[m",
+            group.paths().len(),
+            group.size(),
+            group.wasted(),
+        )?;
+        for path in head {
+            writeln!(out, "[33m  ├[m {}", path.display())?;
+        }
+        writeln!(out, "[33m  └[m {}", tail.display())?;
+    }
+    writeln!(
+        out,
+        "[37mWasted:[m {} bytes in {} groups",
+        groups.iter().map(dircmp::Duplicates::wasted).sum::<u64>(),
+        groups.len(),
+    )?;
+
+    Ok(())
+}
@@ -7,29 +7,170 @@ pub enum Error {
     Write(bincode::Error),
     #[error("Could not read entries: {0}")]
     Read(bincode::Error),
+    #[error("Not a file written by dircmp, or by a version that did not record its format")]
+    Foreign,
+    #[error("File was written in format version {0}, expected {VERSION}")]
+    Version(u8),
+    #[error("File holds an unknown layout {0}")]
+    Unknown(u8),
+    #[error("Expected a {expected}, but the file holds a {found}")]
+    Layout { expected: Layout, found: Layout },
     #[error("Could not write to summary: {0}")]
     Summary(std::io::Error),
     #[error("Could not write to stdout: {0}")]
     Print(std::io::Error),
 }
 
-pub fn from_binary(input: impl std::io::Read) -> Result<Dirs> {
+/// Starts every file written by [`to_binary`]
+const MAGIC: &[u8; 6] = b"dircmp";
+
+/// Bumped whenever the encoding of the stored values changes
+const VERSION: u8 = 3;
+
+/// What a file written by [`to_binary`] holds, recorded in its header after [`MAGIC`] and
+/// [`VERSION`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Layout {
+    /// Two linked directories, written by `scan` of two sides
+    Pair,
+    /// Written by `scan` of more than two sides, or with `--matrix`
+    Matrix,
+    /// A single directory, written by `index`
+    Manifest,
+}
+
+impl Layout {
+    fn tag(self) -> u8 {
+        match self {
+            Self::Pair => 0,
+            Self::Matrix => 1,
+            Self::Manifest => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Pair),
+            1 => Some(Self::Matrix),
+            2 => Some(Self::Manifest),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pair => f.write_str("comparison of two sides"),
+            Self::Matrix => f.write_str("matrix"),
+            Self::Manifest => f.write_str("manifest"),
+        }
+    }
+}
+
+/// A value [`to_binary`] can write, along with the [`Layout`] telling it apart
+pub trait Stored: serde::Serialize + serde::de::DeserializeOwned {
+    const LAYOUT: Layout;
+}
+
+impl Stored for Dirs {
+    const LAYOUT: Layout = Layout::Pair;
+}
+
+impl Stored for dircmp::Matrix {
+    const LAYOUT: Layout = Layout::Matrix;
+}
+
+impl Stored for dircmp::Directory {
+    const LAYOUT: Layout = Layout::Manifest;
+}
+
+/// The results of a `scan`, in either layout
+pub enum Results {
+    Pair(Box<Dirs>),
+    Matrix(dircmp::Matrix),
+}
+
+/// Reads the results of a `scan`, whichever layout they were written in
+pub fn results_from_binary(input: impl std::io::Read) -> Result<Results> {
+    let mut reader = std::io::BufReader::new(input);
+    match header(&mut reader)? {
+        Layout::Pair => read(reader).map(|dirs| Results::Pair(Box::new(dirs))),
+        Layout::Matrix => read(reader).map(Results::Matrix),
+        found @ Layout::Manifest => Err(Error::Layout {
+            expected: Layout::Pair,
+            found,
+        }),
+    }
+}
+
+pub fn from_binary<T: Stored>(input: impl std::io::Read) -> Result<T> {
+    let mut reader = std::io::BufReader::new(input);
+    match header(&mut reader)? {
+        layout if layout == T::LAYOUT => read(reader),
+        found => Err(Error::Layout {
+            expected: T::LAYOUT,
+            found,
+        }),
+    }
+}
+
+/// Reads the header written by [`to_binary`], returning the layout of what follows
+fn header(reader: &mut impl std::io::Read) -> Result<Layout> {
+    let mut header = [0; MAGIC.len() + 2];
+    reader.read_exact(&mut header).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => Error::Foreign,
+        _ => Error::Read(e.into()),
+    })?;
+
+    if header[..MAGIC.len()] != MAGIC[..] {
+        return Err(Error::Foreign);
+    }
+    let [.., version, tag] = header;
+    if version != VERSION {
+        return Err(Error::Version(version));
+    }
+    Layout::from_tag(tag).ok_or(Error::Unknown(tag))
+}
+
+fn read<T: serde::de::DeserializeOwned>(reader: impl std::io::Read) -> Result<T> {
     let start = std::time::Instant::now();
 
     log::info!("Reading from input file");
-    let reader = std::io::BufReader::new(input);
-    let dirs = bincode::deserialize_from(reader).map_err(Error::Read)?;
+    let value = bincode::deserialize_from(reader).map_err(Error::Read)?;
     log::info!("Finished reading from input file in {:?}", start.elapsed());
 
-    Ok(dirs)
+    Ok(value)
 }
 
-pub fn to_binary(output: impl std::io::Write, dirs: &Dirs) -> Result {
+/// Writes `value` next to `path` first and then moves it there, so that an interrupted write
+/// never leaves a truncated file behind
+pub fn to_binary<T: Stored>(path: &std::path::Path, value: &T) -> Result {
+    use std::io::Write;
+
     let start = std::time::Instant::now();
 
-    let writer = std::io::BufWriter::new(output);
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = std::path::PathBuf::from(temporary);
+
     log::info!("Writing to output file");
-    bincode::serialize_into(writer, dirs).map_err(Error::Write)?;
+    let result = std::fs::File::create(&temporary)
+        .map_err(|e| Error::Write(e.into()))
+        .and_then(|file| {
+            let mut writer = std::io::BufWriter::new(file);
+            writer
+                .write_all(MAGIC)
+                .and_then(|()| writer.write_all(&[VERSION, T::LAYOUT.tag()]))
+                .map_err(|e| Error::Write(e.into()))?;
+            bincode::serialize_into(&mut writer, value).map_err(Error::Write)?;
+            writer.flush().map_err(|e| Error::Write(e.into()))
+        })
+        .and_then(|()| std::fs::rename(&temporary, path).map_err(|e| Error::Write(e.into())));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result?;
     log::info!("Finished writing to output file in {:?}", start.elapsed());
 
     Ok(())
@@ -47,9 +188,103 @@ pub fn to_summary(output: impl std::io::Write, dirs: &Dirs) -> Result {
     Ok(())
 }
 
-pub fn to_stdout(dirs: &Dirs, show_matched: bool) -> Result {
-    write_pretty(dirs, show_matched, Mode::Left).map_err(Error::Print)?;
-    write_pretty(dirs, show_matched, Mode::Right).map_err(Error::Print)
+/// Restricts and orders the modified pairs shown on stdout by their similarity score
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Similarity {
+    /// List modified pairs first, least similar first
+    pub sort: bool,
+    pub min: Option<u8>,
+    pub max: Option<u8>,
+}
+
+impl Similarity {
+    /// Pairs that were not scored are always shown
+    fn keeps(self, status: &dircmp::Status) -> bool {
+        let dircmp::Status::Modified(_, Some(score)) = status else {
+            return true;
+        };
+        self.min.is_none_or(|min| *score >= min) && self.max.is_none_or(|max| *score <= max)
+    }
+
+    fn order(self, entries: &[dircmp::Entry]) -> Vec<&dircmp::Entry> {
+        let mut entries = entries
+            .iter()
+            .filter(|e| self.keeps(e.status()))
+            .collect::<Vec<_>>();
+        if self.sort {
+            // Stable, so everything else keeps its order after the scored pairs
+            entries.sort_by_key(|e| match e.status() {
+                dircmp::Status::Modified(_, Some(score)) => *score,
+                _ => u8::MAX,
+            });
+        }
+        entries
+    }
+}
+
+pub fn to_stdout(dirs: &Dirs, show_matched: bool, similarity: Similarity) -> Result {
+    write_pretty(dirs, show_matched, similarity, Mode::Left).map_err(Error::Print)?;
+    write_pretty(dirs, show_matched, similarity, Mode::Right).map_err(Error::Print)
+}
+
+pub fn to_matrix_summary(output: impl std::io::Write, matrix: &dircmp::Matrix) -> Result {
+    let start = std::time::Instant::now();
+
+    let mut writer = std::io::BufWriter::new(output);
+    log::info!("Writing summary");
+    write_matrix_tsv(&mut writer, matrix).map_err(Error::Summary)?;
+    log::info!("Finished writing summary in {:?}", start.elapsed());
+
+    Ok(())
+}
+
+pub fn to_matrix_stdout(matrix: &dircmp::Matrix, show_matched: bool) -> Result {
+    write_matrix_pretty(matrix, show_matched).map_err(Error::Print)
+}
+
+fn write_matrix_tsv(mut out: impl std::io::Write, matrix: &dircmp::Matrix) -> std::io::Result<()> {
+    for row in matrix.rows() {
+        let agreement = row.agreement();
+        if agreement == dircmp::Agreement::Same {
+            continue;
+        }
+
+        write!(out, "{path}	{agreement}", path = row.path().display())?;
+        for cell in row.cells() {
+            write!(out, "	{}", Celled(*cell))?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_matrix_pretty(matrix: &dircmp::Matrix, show_matched: bool) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "[37mSides:[m")?;
+    for (i, side) in matrix.sides().iter().enumerate() {
+        writeln!(out, "[37m  {}[m {}", i + 1, side.display())?;
+    }
+
+    for row in matrix.rows() {
+        let agreement = row.agreement();
+        let color = match agreement {
+            dircmp::Agreement::Same if !show_matched => continue,
+            dircmp::Agreement::Same => 32,
+            dircmp::Agreement::Missing => 31,
+            dircmp::Agreement::Diverged => 35,
+        };
+
+        write!(out, "[{color}m{agreement:<8}[m")?;
+        for cell in row.cells() {
+            write!(out, " {}", Celled(*cell))?;
+        }
+        writeln!(out, " {}", row.path().display())?;
+    }
+
+    Ok(())
 }
 
 fn write_tsv(mut out: impl std::io::Write, dirs: &Dirs, mode: Mode) -> std::io::Result<()> {
@@ -59,13 +294,16 @@ fn write_tsv(mut out: impl std::io::Write, dirs: &Dirs, mode: Mode) -> std::io::
     };
 
     for entry in reference.entries() {
+        let flags = entry.flags();
         match entry.status() {
-            dircmp::Status::Same(_) => {}
-            status @ (dircmp::Status::Moved(i) | dircmp::Status::Modified(i)) => {
+            dircmp::Status::Same(_) if flags.is_empty() => {}
+            status @ (dircmp::Status::Same(i)
+            | dircmp::Status::Moved(i)
+            | dircmp::Status::Modified(i, None)) => {
                 if mode == Mode::Left {
                     writeln!(
                         out,
-                        "{path}	{status}	{other}",
+                        "{path}	{status}{flags}	{other}",
                         path = reference.path().join(entry.path()).display(),
                         other = other
                             .path()
@@ -77,7 +315,7 @@ fn write_tsv(mut out: impl std::io::Write, dirs: &Dirs, mode: Mode) -> std::io::
             status @ dircmp::Status::Maybe(indices) => {
                 write!(
                     out,
-                    "{path}	{status}",
+                    "{path}	{status}{flags}",
                     path = reference.path().join(entry.path()).display()
                 )?;
                 for i in indices {
@@ -92,10 +330,47 @@ fn write_tsv(mut out: impl std::io::Write, dirs: &Dirs, mode: Mode) -> std::io::
                 }
                 writeln!(out)?;
             }
+            status @ dircmp::Status::Modified(i, Some(score)) => {
+                if mode == Mode::Left {
+                    writeln!(
+                        out,
+                        "{path}	{status}{flags}	{other}	{score}",
+                        path = reference.path().join(entry.path()).display(),
+                        other = other
+                            .path()
+                            .join(unsafe { other.entries().get_unchecked(*i).path() })
+                            .display()
+                    )?;
+                }
+            }
+            status @ (dircmp::Status::Appended(i, delta) | dircmp::Status::Truncated(i, delta)) => {
+                if mode == Mode::Left {
+                    writeln!(
+                        out,
+                        "{path}	{status}{flags}	{other}	{delta}",
+                        path = reference.path().join(entry.path()).display(),
+                        other = other
+                            .path()
+                            .join(unsafe { other.entries().get_unchecked(*i).path() })
+                            .display()
+                    )?;
+                }
+            }
+            status @ dircmp::Status::TypeChanged(kind) => {
+                // Directories have no entries, so the change is only visible from this side
+                if mode == Mode::Left || *kind == dircmp::Kind::Directory {
+                    writeln!(
+                        out,
+                        "{path}	{status}{flags}	{from}	{kind}",
+                        path = reference.path().join(entry.path()).display(),
+                        from = entry.kind(),
+                    )?;
+                }
+            }
             status @ (dircmp::Status::Unique | dircmp::Status::Empty) => {
                 writeln!(
                     out,
-                    "{path}	{status}",
+                    "{path}	{status}{flags}",
                     path = reference.path().join(entry.path()).display(),
                 )?;
             }
@@ -105,7 +380,12 @@ fn write_tsv(mut out: impl std::io::Write, dirs: &Dirs, mode: Mode) -> std::io::
     Ok(())
 }
 
-fn write_pretty(dirs: &Dirs, show_matched: bool, mode: Mode) -> std::io::Result<()> {
+fn write_pretty(
+    dirs: &Dirs,
+    show_matched: bool,
+    similarity: Similarity,
+    mode: Mode,
+) -> std::io::Result<()> {
     use std::io::Write;
 
     let (reference, other) = match mode {
@@ -115,34 +395,65 @@ fn write_pretty(dirs: &Dirs, show_matched: bool, mode: Mode) -> std::io::Result<
 
     let mut out = std::io::stdout().lock();
     writeln!(out, "[37mVisiting:[m {}", reference.path().display())?;
-    for entry in reference.entries() {
+    for entry in similarity.order(reference.entries()) {
+        let flags = Flagged(entry.flags());
+        let path = entry.path().display();
         match entry.status() {
             status @ dircmp::Status::Same(_) => {
-                if show_matched && mode == Mode::Left {
-                    writeln!(out, "[32m{mode} {status:<8}[m {}", entry.path().display())?;
+                if (show_matched || !entry.flags().is_empty()) && mode == Mode::Left {
+                    writeln!(out, "[32m{mode} {status:<8}[m {path}{flags}")?;
                 }
             }
             status @ dircmp::Status::Moved(i) => {
                 if mode == Mode::Left {
-                    writeln!(out, "[33m{mode} {status:<8}[m {}", entry.path().display())?;
+                    writeln!(out, "[33m{mode} {status:<8}[m {path}{flags}")?;
                     writeln!(out, "[33m  └[m {}", unsafe {
                         other.entries().get_unchecked(*i).path().display()
                     })?;
                 }
             }
-            status @ dircmp::Status::Modified(i) => {
+            status @ dircmp::Status::Modified(i, score) => {
                 if mode == Mode::Left {
-                    writeln!(out, "[35m{mode} {status:<8}[m {}", entry.path().display())?;
-                    writeln!(out, "[35m  └[m {}", unsafe {
+                    writeln!(out, "[35m{mode} {status:<8}[m {path}{flags}")?;
+                    write!(out, "[35m  └[m {}", unsafe {
                         other.entries().get_unchecked(*i).path().display()
                     })?;
+                    match score {
+                        Some(score) => writeln!(out, " [37m({score}% similar)[m")?,
+                        None => writeln!(out)?,
+                    }
+                }
+            }
+            status @ (dircmp::Status::Appended(i, delta) | dircmp::Status::Truncated(i, delta)) => {
+                if mode == Mode::Left {
+                    let sign = if matches!(status, dircmp::Status::Appended(..)) {
+                        '+'
+                    } else {
+                        '-'
+                    };
+                    writeln!(out, "[35m{mode} {status:<8}[m {path}{flags}")?;
+                    writeln!(
+                        out,
+                        "[35m  └[m {} [37m({sign}{delta} bytes)[m",
+                        unsafe { other.entries().get_unchecked(*i).path().display() }
+                    )?;
+                }
+            }
+            status @ dircmp::Status::TypeChanged(kind) => {
+                // Directories have no entries, so the change is only visible from this side
+                if mode == Mode::Left || *kind == dircmp::Kind::Directory {
+                    writeln!(
+                        out,
+                        "[35m{mode} {status:<8}[m {path} [37m({} -> {kind})[m{flags}",
+                        entry.kind(),
+                    )?;
                 }
             }
             status @ dircmp::Status::Maybe(indices) => {
                 let Some((tail, head)) = indices.split_last() else {
                     continue;
                 };
-                writeln!(out, "[34m{mode} {status:<8}[m {}", entry.path().display())?;
+                writeln!(out, "[34m{mode} {status:<8}[m {path}")?;
                 for i in head {
                     writeln!(out, "[34m  ├[m {}", unsafe {
                         other.entries().get_unchecked(*i).path().display()
@@ -153,10 +464,10 @@ fn write_pretty(dirs: &Dirs, show_matched: bool, mode: Mode) -> std::io::Result<
                 })?;
             }
             status @ dircmp::Status::Unique => {
-                writeln!(out, "[31m{mode} {status:<8}[m {}", entry.path().display())?;
+                writeln!(out, "[31m{mode} {status:<8}[m {path}{flags}")?;
             }
             status @ dircmp::Status::Empty => {
-                writeln!(out, "[36m{mode} {status:<8}[m {}", entry.path().display())?;
+                writeln!(out, "[36m{mode} {status:<8}[m {path}{flags}")?;
             }
         }
     }
@@ -178,3 +489,36 @@ impl std::fmt::Display for Mode {
         }
     }
 }
+
+struct Flagged(dircmp::Flags);
+
+impl std::fmt::Display for Flagged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            write!(f, " [37m{}[m", self.0)
+        }
+    }
+}
+
+/// Shows a cell of a matrix as the letter of its content group, `A` being held by the most sides,
+/// `~` if the side holds the content at another path, and `-` if it does not hold it at all
+struct Celled(dircmp::Cell);
+
+impl std::fmt::Display for Celled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use std::fmt::Write;
+
+        match self.0 {
+            dircmp::Cell::Group(group) => f.write_char(
+                u8::try_from(group)
+                    .ok()
+                    .filter(|group| *group < 26)
+                    .map_or('*', |group| char::from(b'A' + group)),
+            ),
+            dircmp::Cell::Elsewhere => f.write_char('~'),
+            dircmp::Cell::Absent => f.write_char('-'),
+        }
+    }
+}
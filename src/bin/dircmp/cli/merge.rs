@@ -0,0 +1,182 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not write to summary: {0}")]
+    Summary(std::io::Error),
+    #[error("Could not write conflicts: {0}")]
+    Conflicts(std::io::Error),
+    #[error("Could not write to stdout: {0}")]
+    Print(std::io::Error),
+}
+
+pub fn to_summary(output: impl std::io::Write, merge: &dircmp::Merge) -> Result<(), Error> {
+    let mut writer = std::io::BufWriter::new(output);
+    write_tsv(&mut writer, merge).map_err(Error::Summary)
+}
+
+pub fn to_conflicts(output: impl std::io::Write, merge: &dircmp::Merge) -> Result<(), Error> {
+    let mut writer = std::io::BufWriter::new(output);
+    write_conflicts(&mut writer, merge).map_err(Error::Conflicts)
+}
+
+pub fn to_stdout(merge: &dircmp::Merge, show_unchanged: bool) -> Result<(), Error> {
+    write_pretty(merge, show_unchanged).map_err(Error::Print)
+}
+
+/// What a side did to an entry of `base`, or whether it added one
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Action {
+    /// The side holds nothing where the other added an entry
+    Absent,
+    Kept,
+    Added,
+    Modified,
+    Moved,
+    Deleted,
+}
+
+impl Action {
+    fn of(base: Option<&dircmp::Entry>, side: Option<&dircmp::Entry>) -> Self {
+        match (base, side) {
+            (Some(_), None) => Self::Deleted,
+            (None, Some(_)) => Self::Added,
+            (Some(base), Some(side))
+                if base.hash() != side.hash() || base.kind() != side.kind() =>
+            {
+                Self::Modified
+            }
+            (Some(base), Some(side)) if base.path() != side.path() => Self::Moved,
+            (Some(_), Some(_)) => Self::Kept,
+            (None, None) => Self::Absent,
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Absent => f.pad("ABSENT"),
+            Self::Kept => f.pad("KEPT"),
+            Self::Added => f.pad("ADDED"),
+            Self::Modified => f.pad("MODIFIED"),
+            Self::Moved => f.pad("MOVED"),
+            Self::Deleted => f.pad("DELETED"),
+        }
+    }
+}
+
+/// The path a change is known by, in `base` if it has one
+fn path<'a>(
+    (base, left, right): (
+        Option<&'a dircmp::Entry>,
+        Option<&'a dircmp::Entry>,
+        Option<&'a dircmp::Entry>,
+    ),
+) -> &'a std::path::Path {
+    base.or(left)
+        .or(right)
+        .map_or(std::path::Path::new(""), dircmp::Entry::path)
+}
+
+fn write_tsv(mut out: impl std::io::Write, merge: &dircmp::Merge) -> std::io::Result<()> {
+    for change in merge.changes() {
+        let outcome = change.outcome();
+        if outcome == dircmp::Outcome::Unchanged {
+            continue;
+        }
+
+        let entries @ (base, left, right) = merge.entries(change);
+        write!(out, "{}	{outcome}", path(entries).display())?;
+        if let dircmp::Outcome::Conflict(conflict) = outcome {
+            write!(out, " ({conflict})")?;
+        }
+        for (root, side) in [(merge.left().path(), left), (merge.right().path(), right)] {
+            write!(
+                out,
+                "	{action}	{path}",
+                action = Action::of(base, side),
+                path = side.map_or_else(String::new, |side| {
+                    root.join(side.path()).display().to_string()
+                }),
+            )?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_conflicts(mut out: impl std::io::Write, merge: &dircmp::Merge) -> std::io::Result<()> {
+    for change in merge.changes() {
+        let dircmp::Outcome::Conflict(conflict) = change.outcome() else {
+            continue;
+        };
+
+        let entries @ (_, left, right) = merge.entries(change);
+        writeln!(
+            out,
+            "{path}	{conflict}	{left}	{right}",
+            path = path(entries).display(),
+            left = left.map_or_else(String::new, |left| {
+                merge.left().path().join(left.path()).display().to_string()
+            }),
+            right = right.map_or_else(String::new, |right| {
+                merge
+                    .right()
+                    .path()
+                    .join(right.path())
+                    .display()
+                    .to_string()
+            }),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_pretty(merge: &dircmp::Merge, show_unchanged: bool) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "[37mMerging:[m {}", merge.base().path().display())?;
+    writeln!(out, "[37m  <[m {}", merge.left().path().display())?;
+    writeln!(out, "[37m  >[m {}", merge.right().path().display())?;
+
+    let mut conflicts = Vec::new();
+    for change in merge.changes() {
+        let outcome = change.outcome();
+        let color = match outcome {
+            dircmp::Outcome::Unchanged if !show_unchanged => continue,
+            dircmp::Outcome::Unchanged => 32,
+            dircmp::Outcome::Left | dircmp::Outcome::Right => 33,
+            dircmp::Outcome::Both | dircmp::Outcome::Combined => 36,
+            dircmp::Outcome::Conflict(conflict) => {
+                conflicts.push((path(merge.entries(change)), conflict));
+                31
+            }
+        };
+
+        let entries @ (base, left, right) = merge.entries(change);
+        writeln!(out, "[{color}m{outcome:<9}[m {}", path(entries).display())?;
+        for (mode, side) in [('<', left), ('>', right)] {
+            match Action::of(base, side) {
+                Action::Absent | Action::Kept => {}
+                action @ Action::Deleted => writeln!(out, "[{color}m  {mode} {action}[m")?,
+                action => writeln!(
+                    out,
+                    "[{color}m  {mode} {action:<8}[m {}",
+                    side.map_or(std::path::Path::new(""), dircmp::Entry::path)
+                        .display()
+                )?,
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        writeln!(out, "[37mConflicts:[m {}", conflicts.len())?;
+        for (path, conflict) in conflicts {
+            writeln!(out, "[31m  {conflict:<20}[m {}", path.display())?;
+        }
+    }
+
+    Ok(())
+}
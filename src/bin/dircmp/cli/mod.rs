@@ -1,5 +1,10 @@
 mod copy;
+mod dedup;
+mod diff;
+mod dupes;
 mod io;
+mod merge;
+mod verify;
 
 use super::args;
 
@@ -11,6 +16,22 @@ pub enum Error {
     Io(#[from] io::Error),
     #[error(transparent)]
     Copy(#[from] copy::Error),
+    #[error(transparent)]
+    Diff(#[from] diff::Error),
+    #[error(transparent)]
+    Dupes(#[from] dupes::Error),
+    #[error(transparent)]
+    Dedup(#[from] dedup::Error),
+    #[error(transparent)]
+    Merge(#[from] merge::Error),
+    #[error(transparent)]
+    Verify(#[from] verify::Error),
+    #[error("Found {0} corrupted files")]
+    Corrupted(usize),
+    #[error("Found {0} conflicts")]
+    Conflicts(usize),
+    #[error("Only directories can be merged into a target")]
+    NotDirectories,
 }
 
 pub fn run(args: args::Command) -> std::process::ExitCode {
@@ -22,8 +43,16 @@ pub fn run(args: args::Command) -> std::process::ExitCode {
     let start = std::time::Instant::now();
     if let Err(e) = match args {
         args::Command::Scan(args) => scan(args),
+        args::Command::Index(args) => index(args),
         args::Command::Print(args) => print(args),
         args::Command::Copy(args) => copy(args),
+        args::Command::Merge(args) => merge(args),
+        args::Command::Diff(args) => diff(args),
+        args::Command::Dupes(args) => dupes(args),
+        args::Command::Dedup(args) => dedup(args),
+        args::Command::Verify(args) => verify(args),
+        args::Command::Export(args) => export(args),
+        args::Command::Agent(args) => agent(args),
     } {
         log::error!("{e}");
         return std::process::ExitCode::FAILURE;
@@ -61,35 +90,175 @@ fn scan(
         print: print_filter,
         left,
         right,
+        more,
+        matrix,
         output,
         summary,
+        options,
         verbosity: _,
     }: args::Scan,
 ) -> Result<(), Error> {
     log::debug!(
-        "left: {left}, right: {right}, output: {output}, summary: {summary}, print_filter: {print_filter:?}",
-        left = left.display(),
-        right = right.display(),
+        "left: {left:?}, right: {right:?}, output: {output}, summary: {summary}, print_filter: {print_filter:?}",
         output = output.is_some(),
         summary = summary.is_some(),
     );
 
-    let dirs = dircmp::compare(left, right)?;
+    let digest = options.hashing.digest.is_some();
+    let mut options = dircmp::Options::from(options);
+    let show_matched = match print_filter {
+        args::PrintFilter::None => None,
+        args::PrintFilter::Diff => Some(false),
+        args::PrintFilter::All => Some(true),
+    };
+
+    if matrix || !more.is_empty() {
+        let sides = [left, right].into_iter().chain(more).collect();
+        let matrix = dircmp::link_all(sides_with(sides, digest, &mut options)?, &options)?;
+
+        if let Some(output) = output {
+            io::to_binary(&output, &matrix)?;
+        }
+        if let Some(output) = summary {
+            io::to_matrix_summary(output.as_ref(), &matrix)?;
+        }
+        if let Some(show_matched) = show_matched {
+            io::to_matrix_stdout(&matrix, show_matched)?;
+        }
+        return Ok(());
+    }
+
+    let mut sides = sides_with(vec![left, right], digest, &mut options)?.into_iter();
+    let (Some(left), Some(right)) = (sides.next(), sides.next()) else {
+        unreachable!("Two sides are loaded from two sides");
+    };
+    let dirs = dircmp::link(left, right, &options)?;
 
     if let Some(output) = output {
-        io::to_binary(output.as_ref(), &dirs)?;
+        io::to_binary(&output, &dirs)?;
     }
 
     if let Some(output) = summary {
         io::to_summary(output.as_ref(), &dirs)?;
     }
 
-    let show_matched = match print_filter {
-        args::PrintFilter::None => return Ok(()),
-        args::PrintFilter::Diff => false,
-        args::PrintFilter::All => true,
+    if let Some(show_matched) = show_matched {
+        io::to_stdout(&dirs, show_matched, io::Similarity::default())?;
+    }
+    Ok(())
+}
+
+/// Loads the sides in order, except that the first one that is not a live directory is read
+/// first, so the live ones can be hashed with its digest
+fn sides_with(
+    sides: Vec<args::Side>,
+    digest: bool,
+    options: &mut dircmp::Options,
+) -> Result<Vec<dircmp::Directory>, Error> {
+    let first = sides
+        .iter()
+        .position(|side| !matches!(side, args::Side::Directory(_)))
+        .unwrap_or_default();
+
+    let mut unloaded = sides.into_iter().map(Some).collect::<Vec<_>>();
+    let mut loaded = std::iter::repeat_with(|| None)
+        .take(unloaded.len())
+        .collect::<Vec<_>>();
+    if let Some(first_side) = unloaded[first].take() {
+        let directory = side(first_side, options)?;
+        if !digest {
+            options.algorithm = directory.settings().algorithm;
+        }
+        loaded[first] = Some(directory);
+    }
+    for (i, other) in unloaded.into_iter().enumerate() {
+        if let Some(other) = other {
+            loaded[i] = Some(side(other, options)?);
+        }
+    }
+
+    Ok(loaded.into_iter().flatten().collect())
+}
+
+fn index(
+    args::Index {
+        verbosity: _,
+        path,
+        output,
+        options,
+    }: args::Index,
+) -> Result<(), Error> {
+    log::debug!("path: {path}", path = path.display());
+
+    let directory = dircmp::index(path, &options.into())?;
+    io::to_binary(&output, &directory)?;
+    Ok(())
+}
+
+fn side(side: args::Side, options: &dircmp::Options) -> Result<dircmp::Directory, Error> {
+    match side {
+        args::Side::Directory(path) | args::Side::Archive(path) => {
+            Ok(dircmp::index(path, options)?)
+        }
+        args::Side::Manifest(input) => Ok(io::from_binary(input.as_ref())?),
+        args::Side::Checksums(path, input) => Ok(dircmp::read_checksums(
+            std::io::BufReader::new(input.as_ref()),
+            path.parent().unwrap_or(&path).to_path_buf(),
+        )?),
+        args::Side::Mtree(path, input) => Ok(dircmp::read_mtree(
+            std::io::BufReader::new(input.as_ref()),
+            path.parent().unwrap_or(&path).to_path_buf(),
+        )?),
+        args::Side::Git(path, revision) => Ok(dircmp::index_git(path, &revision, options)?),
+        args::Side::Command(command) => {
+            let mut shell = std::process::Command::new("sh");
+            shell.arg("-c").arg(command);
+            Ok(dircmp::index_remote(shell)?)
+        }
+    }
+}
+
+fn agent(
+    args::Agent {
+        verbosity: _,
+        path,
+        options,
+    }: args::Agent,
+) -> Result<(), Error> {
+    log::debug!("path: {path}", path = path.display());
+
+    dircmp::serve(
+        &path,
+        &options.into(),
+        std::io::stdin().lock(),
+        std::io::BufWriter::new(std::io::stdout()),
+    )?;
+    Ok(())
+}
+
+fn export(
+    args::Export {
+        verbosity: _,
+        side: exported,
+        format,
+        output,
+        options,
+    }: args::Export,
+) -> Result<(), Error> {
+    log::debug!(
+        "side: {exported:?}, format: {format:?}, output: {output}",
+        output = output.is_some(),
+    );
+
+    let directory = side(exported, &options.into())?;
+    let writer: Box<dyn std::io::Write> = match output {
+        Some(output) => Box::new(std::io::BufWriter::new(output)),
+        None => Box::new(std::io::stdout().lock()),
     };
-    io::to_stdout(&dirs, show_matched)?;
+    match format {
+        args::Format::Sums => dircmp::write_checksums(&directory, writer)?,
+        args::Format::Mtree => dircmp::write_mtree(&directory, writer)?,
+    }
     Ok(())
 }
 
@@ -98,6 +267,9 @@ fn print(
         input,
         summary,
         matched: show_matched,
+        sort_similarity,
+        min_similarity,
+        max_similarity,
         verbosity: _,
     }: args::Print,
 ) -> Result<(), Error> {
@@ -106,13 +278,30 @@ fn print(
         summary = summary.is_some(),
     );
 
-    let dirs = io::from_binary(input.as_ref())?;
+    let dirs = match io::results_from_binary(input.as_ref())? {
+        io::Results::Pair(dirs) => *dirs,
+        io::Results::Matrix(matrix) => {
+            if sort_similarity || min_similarity.is_some() || max_similarity.is_some() {
+                log::warn!("Ignoring the similarity filters, the results are a matrix");
+            }
+            if let Some(output) = summary {
+                io::to_matrix_summary(output.as_ref(), &matrix)?;
+            }
+            io::to_matrix_stdout(&matrix, show_matched)?;
+            return Ok(());
+        }
+    };
 
     if let Some(output) = summary {
         io::to_summary(output.as_ref(), &dirs)?;
     }
 
-    io::to_stdout(&dirs, show_matched)?;
+    let similarity = io::Similarity {
+        sort: sort_similarity,
+        min: min_similarity,
+        max: max_similarity,
+    };
+    io::to_stdout(&dirs, show_matched, similarity)?;
 
     Ok(())
 }
@@ -123,6 +312,7 @@ fn copy(
         reference,
         derived,
         target,
+        options,
     }: args::Copy,
 ) -> Result<(), Error> {
     log::debug!(
@@ -132,10 +322,15 @@ fn copy(
         target = target.display(),
     );
 
-    let (reference, derived) = dircmp::compare(reference, derived)?;
+    let mut options = dircmp::Options::from(options);
+    if options.into_archives {
+        log::warn!("Ignoring --into-archives, members of archives cannot be copied");
+        options.into_archives = false;
+    }
+    let (reference, derived) = dircmp::compare(reference, derived, &options)?;
 
     let start = std::time::Instant::now();
-    let entries = copy::copy(reference, derived, &target)?;
+    let entries = copy::copy(reference, derived, &target, &options)?;
     log::info!(
         "Finished copying {entries} files into {} in {:?}",
         target.display(),
@@ -144,3 +339,226 @@ fn copy(
 
     Ok(())
 }
+
+fn merge(
+    args::Merge {
+        verbosity: _,
+        print: print_filter,
+        base,
+        left,
+        right,
+        target,
+        summary,
+        conflicts,
+        options,
+    }: args::Merge,
+) -> Result<(), Error> {
+    log::debug!(
+        "base: {base:?}, left: {left:?}, right: {right:?}, target: {target:?}, summary: {summary}, conflicts: {conflicts}, print_filter: {print_filter:?}",
+        summary = summary.is_some(),
+        conflicts = conflicts.is_some(),
+    );
+
+    let digest = options.hashing.digest.is_some();
+    let mut options = dircmp::Options::from(options);
+    if target.is_some() {
+        if !matches!(
+            (&left, &right),
+            (args::Side::Directory(_), args::Side::Directory(_))
+        ) {
+            return Err(Error::NotDirectories);
+        }
+        if options.into_archives {
+            log::warn!("Ignoring --into-archives, members of archives cannot be copied");
+            options.into_archives = false;
+        }
+    }
+    let mut sides = sides_with(vec![base, left, right], digest, &mut options)?.into_iter();
+    let (Some(base), Some(left), Some(right)) = (sides.next(), sides.next(), sides.next()) else {
+        unreachable!("Three sides are loaded from three sides");
+    };
+    let merged = dircmp::link_three(base, left, right, &options)?;
+
+    if let Some(target) = target {
+        let start = std::time::Instant::now();
+        let (files, conflicts) = copy::merge(&merged, &target, &options)?;
+        log::info!(
+            "Finished merging {files} files into {} with {conflicts} conflicts in {:?}",
+            target.display(),
+            start.elapsed(),
+        );
+    }
+
+    if let Some(output) = summary {
+        merge::to_summary(output.as_ref(), &merged)?;
+    }
+    if let Some(output) = conflicts {
+        merge::to_conflicts(output.as_ref(), &merged)?;
+    }
+    match print_filter {
+        args::PrintFilter::None => {}
+        args::PrintFilter::Diff => merge::to_stdout(&merged, false)?,
+        args::PrintFilter::All => merge::to_stdout(&merged, true)?,
+    }
+
+    match merged
+        .changes()
+        .iter()
+        .filter(|change| matches!(change.outcome(), dircmp::Outcome::Conflict(_)))
+        .count()
+    {
+        0 => Ok(()),
+        conflicts => Err(Error::Conflicts(conflicts)),
+    }
+}
+
+fn diff(
+    args::Diff {
+        verbosity: _,
+        input,
+        left,
+        right,
+        limit,
+        options,
+    }: args::Diff,
+) -> Result<(), Error> {
+    log::debug!(
+        "input: {input}, left: {left:?}, right: {right:?}, limit: {limit}",
+        input = input.is_some(),
+    );
+
+    let dirs = match (input, left, right) {
+        (Some(input), _, _) => io::from_binary(input.as_ref())?,
+        (None, Some(left), Some(right)) => dircmp::compare(left, right, &options.into())?,
+        _ => unreachable!("Arguments are validated by the parser"),
+    };
+
+    let start = std::time::Instant::now();
+    let pairs = diff::diff(&dirs, limit)?;
+    log::info!("Finished diffing {pairs} pairs in {:?}", start.elapsed());
+
+    Ok(())
+}
+
+fn dupes(
+    args::Dupes {
+        verbosity: _,
+        path,
+        summary,
+        options,
+    }: args::Dupes,
+) -> Result<(), Error> {
+    log::debug!(
+        "path: {path}, summary: {summary}",
+        path = path.display(),
+        summary = summary.is_some(),
+    );
+
+    let groups = dircmp::dupes(&path, &options.into())?;
+
+    if let Some(output) = summary {
+        dupes::to_summary(output.as_ref(), &path, &groups)?;
+    }
+
+    dupes::to_stdout(&path, &groups)?;
+    Ok(())
+}
+
+fn dedup(
+    args::Dedup {
+        verbosity: _,
+        path,
+        method,
+        dry_run,
+        journal,
+        undo,
+        options,
+    }: args::Dedup,
+) -> Result<(), Error> {
+    log::debug!(
+        "path: {path:?}, method: {method:?}, dry_run: {dry_run}, journal: {journal:?}, undo: {undo}"
+    );
+
+    let start = std::time::Instant::now();
+    match (path, journal) {
+        (_, Some(journal)) if undo => {
+            let files = dedup::undo(&journal)?;
+            log::info!("Finished restoring {files} files in {:?}", start.elapsed());
+        }
+        (Some(path), journal) => {
+            let groups = dircmp::dupes(&path, &options.into())?;
+            let (files, bytes) = dedup::dedup(&path, &groups, method, journal.as_deref())?;
+            println!(
+                "[37m{}:[m {bytes} bytes in {files} files",
+                if dry_run {
+                    "Would reclaim"
+                } else {
+                    "Reclaimed"
+                }
+            );
+            log::info!("Finished deduplicating in {:?}", start.elapsed());
+        }
+        _ => unreachable!("Arguments are validated by the parser"),
+    }
+
+    Ok(())
+}
+
+fn verify(
+    args::Verify {
+        verbosity: _,
+        path,
+        manifest,
+        summary,
+        options,
+    }: args::Verify,
+) -> Result<(), Error> {
+    log::debug!(
+        "path: {path}, summary: {summary}",
+        path = path.display(),
+        summary = summary.is_some(),
+    );
+
+    let digest = options.hashing.digest.is_some();
+    let decompress = options.hashing.decompress.is_some();
+    let mut options = dircmp::Options::from(options);
+
+    // Hashed like the manifest unless told otherwise, the globs to normalize and canonicalize
+    // cannot be rebuilt from the settings and are left to the warning of `dircmp::verify`
+    let manifest: dircmp::Directory = io::from_binary(manifest.as_ref())?;
+    let settings = manifest.settings();
+    if !digest {
+        options.algorithm = settings.algorithm;
+    }
+    options.attributes |= settings.attributes;
+    if !decompress {
+        options.decompressions = settings
+            .decompressions
+            .iter()
+            .filter_map(|decompression| {
+                let (extension, format) = decompression.split_once('=')?;
+                Some(dircmp::Decompression {
+                    extension: String::from(extension),
+                    format: dircmp::Compression::from_extension(format)?,
+                })
+            })
+            .collect();
+    }
+
+    let verdicts = dircmp::verify(&path, &manifest, &options)?;
+
+    if let Some(output) = summary {
+        verify::to_summary(output.as_ref(), &path, &verdicts)?;
+    }
+
+    verify::to_stdout(&path, &verdicts)?;
+
+    match verdicts
+        .iter()
+        .filter(|(_, v)| *v == dircmp::Verdict::Corrupted)
+        .count()
+    {
+        0 => Ok(()),
+        corrupted => Err(Error::Corrupted(corrupted)),
+    }
+}
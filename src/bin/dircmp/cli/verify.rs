@@ -0,0 +1,52 @@
+type Verdicts = [(std::path::PathBuf, dircmp::Verdict)];
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not write to summary: {0}")]
+    Summary(std::io::Error),
+    #[error("Could not write to stdout: {0}")]
+    Print(std::io::Error),
+}
+
+pub fn to_summary(
+    output: impl std::io::Write,
+    base: &std::path::Path,
+    verdicts: &Verdicts,
+) -> Result<(), Error> {
+    let mut writer = std::io::BufWriter::new(output);
+    write_tsv(&mut writer, base, verdicts).map_err(Error::Summary)
+}
+
+pub fn to_stdout(base: &std::path::Path, verdicts: &Verdicts) -> Result<(), Error> {
+    write_pretty(base, verdicts).map_err(Error::Print)
+}
+
+fn write_tsv(
+    mut out: impl std::io::Write,
+    base: &std::path::Path,
+    verdicts: &Verdicts,
+) -> std::io::Result<()> {
+    for (path, verdict) in verdicts {
+        writeln!(out, "{}\t{verdict}", base.join(path).display())?;
+    }
+
+    Ok(())
+}
+
+fn write_pretty(base: &std::path::Path, verdicts: &Verdicts) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "[37mVerifying:[m {}", base.display())?;
+    for (path, verdict) in verdicts {
+        let color = match verdict {
+            dircmp::Verdict::Corrupted => 31,
+            dircmp::Verdict::Modified => 35,
+            dircmp::Verdict::Missing => 36,
+            dircmp::Verdict::New => 33,
+        };
+        writeln!(out, "[{color}m{verdict:<9}[m {}", path.display())?;
+    }
+
+    Ok(())
+}
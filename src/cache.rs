@@ -0,0 +1,194 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not write hash cache {0}: {1}")]
+    Write(std::path::PathBuf, std::io::Error),
+    #[error("Could not serialize hash cache {0}: {1}")]
+    Serialize(std::path::PathBuf, bincode::Error),
+}
+
+/// Files changed this close to being hashed may change again within the timestamp granularity
+/// of the file system without their stamp changing, so they are not cached
+const RACY: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// What has to be unchanged for a cached hash to still be valid
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Stamp {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    modified: i128,
+    changed: i128,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Record {
+    stamp: Stamp,
+    /// The algorithm and the normalization or canonicalization the hash was computed with
+    transform: String,
+    hash: super::Hash,
+    raw: Option<super::Hash>,
+    size: u64,
+}
+
+/// Hashes of previously read files, keyed by their absolute path
+///
+/// A record is only used while the device, inode, size, modification and change times of the
+/// file, and the algorithm and transform applied to its content, are all the same as when it was
+/// hashed
+#[derive(Debug)]
+pub struct Cache {
+    path: std::path::PathBuf,
+    rehash: bool,
+    records: std::sync::RwLock<std::collections::HashMap<std::path::PathBuf, Record>>,
+    dirty: std::sync::atomic::AtomicBool,
+}
+
+impl Cache {
+    /// Opens the cache configured in the `options`, if any
+    ///
+    /// A missing or unreadable cache file starts an empty cache, it is rebuilt on the next save
+    pub fn open(options: &super::Options) -> Option<std::sync::Arc<Self>> {
+        let path = options.cache.as_ref()?;
+
+        let records = match std::fs::File::open(path) {
+            Ok(file) => bincode::deserialize_from(std::io::BufReader::new(file))
+                .inspect_err(|e| log::warn!("Discarding hash cache {}: {e}", path.display()))
+                .unwrap_or_default(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => std::collections::HashMap::new(),
+            Err(e) => {
+                log::warn!("Discarding hash cache {}: {e}", path.display());
+                std::collections::HashMap::new()
+            }
+        };
+
+        Some(std::sync::Arc::new(Self {
+            path: path.clone(),
+            rehash: options.rehash,
+            records: std::sync::RwLock::new(records),
+            dirty: std::sync::atomic::AtomicBool::new(false),
+        }))
+    }
+
+    /// Returns the cached hash, raw hash and size of the file at `path`, if still valid
+    pub fn get(
+        &self,
+        path: &std::path::Path,
+        metadata: &super::Metadata,
+        transform: &str,
+    ) -> Option<(super::Hash, Option<super::Hash>, u64)> {
+        if self.rehash {
+            return None;
+        }
+
+        let stamp = stamp(metadata)?;
+        let records = self.records.read().ok()?;
+        records
+            .get(path)
+            .filter(|r| r.stamp == stamp && r.transform == transform)
+            .map(|r| (r.hash.clone(), r.raw.clone(), r.size))
+    }
+
+    pub fn insert(
+        &self,
+        path: std::path::PathBuf,
+        metadata: &super::Metadata,
+        transform: String,
+        (hash, raw, size): (super::Hash, Option<super::Hash>, u64),
+    ) {
+        let Some(stamp) = stamp(metadata) else {
+            return;
+        };
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos().cast_signed());
+        if stamp.modified.max(stamp.changed) > now - RACY.as_nanos().cast_signed() {
+            return;
+        }
+
+        if let Ok(mut records) = self.records.write() {
+            records.insert(
+                path,
+                Record {
+                    stamp,
+                    transform,
+                    hash,
+                    raw,
+                    size,
+                },
+            );
+            self.dirty.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    /// Drops the records of files under `base` that are not among the `paths` found there
+    pub fn prune<'a>(
+        &self,
+        base: &std::path::Path,
+        paths: impl Iterator<Item = &'a std::path::Path>,
+    ) {
+        let found = paths
+            .map(|path| base.join(path))
+            .collect::<std::collections::HashSet<_>>();
+
+        if let Ok(mut records) = self.records.write() {
+            let before = records.len();
+            records.retain(|path, _| !path.starts_with(base) || found.contains(path));
+            if records.len() != before {
+                self.dirty.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Writes the cache back if anything changed, replacing the previous file atomically
+    ///
+    /// # Errors
+    ///
+    /// If the cache file cannot be written
+    pub fn save(&self) -> Result<(), Error> {
+        if !self.dirty.load(std::sync::atomic::Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let records = self
+            .records
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| Error::Write(parent.to_path_buf(), e))?;
+        }
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = std::path::PathBuf::from(temporary);
+
+        let file =
+            std::fs::File::create(&temporary).map_err(|e| Error::Write(temporary.clone(), e))?;
+        let mut writer = std::io::BufWriter::new(file);
+        bincode::serialize_into(&mut writer, &*records)
+            .map_err(|e| Error::Serialize(self.path.clone(), e))?;
+        std::io::Write::flush(&mut writer).map_err(|e| Error::Write(temporary.clone(), e))?;
+        std::fs::rename(&temporary, &self.path).map_err(|e| Error::Write(self.path.clone(), e))?;
+
+        log::info!("Saved {} hashes to {}", records.len(), self.path.display());
+        Ok(())
+    }
+}
+
+/// Only complete stamps are usable, a file system that cannot tell when metadata changed is never
+/// cached
+fn stamp(metadata: &super::Metadata) -> Option<Stamp> {
+    let nanos = |time: std::time::SystemTime| match time.duration_since(std::time::UNIX_EPOCH) {
+        Ok(after) => after.as_nanos().cast_signed(),
+        Err(before) => -before.duration().as_nanos().cast_signed(),
+    };
+    let (dev, ino) = metadata.inode?;
+    Some(Stamp {
+        dev,
+        ino,
+        size: metadata.size,
+        modified: nanos(metadata.modified?),
+        changed: nanos(metadata.changed?),
+    })
+}
@@ -0,0 +1,98 @@
+/// Produces the bytes that get hashed in place of the content of a file
+///
+/// This allows files that are equal in meaning but not in bytes, e.g. reformatted JSON, to be
+/// matched
+pub trait Canonicalizer: std::fmt::Debug + Send + Sync {
+    /// Returns the canonical form of the `content`
+    ///
+    /// # Errors
+    ///
+    /// If the `content` could not be read or canonicalized
+    fn canonicalize(&self, content: &mut dyn std::io::Read) -> std::io::Result<Vec<u8>>;
+}
+
+/// Applies the [`Canonicalizer`] to the files matching [`glob`](Self::glob)
+#[derive(Debug, Clone)]
+pub struct Canonicalization {
+    pub glob: glob::Pattern,
+    pub canonicalizer: std::sync::Arc<dyn Canonicalizer>,
+}
+
+/// Parses the content as JSON and writes it back compactly with sorted object keys
+#[derive(Debug, Copy, Clone)]
+pub struct Json;
+
+impl Canonicalizer for Json {
+    fn canonicalize(&self, content: &mut dyn std::io::Read) -> std::io::Result<Vec<u8>> {
+        let value = serde_json::from_reader::<_, serde_json::Value>(content)?;
+        serde_json::to_vec(&value).map_err(std::io::Error::from)
+    }
+}
+
+/// Pipes the content through an external command, hashing its standard output
+#[derive(Debug, Clone)]
+pub struct Command {
+    program: std::ffi::OsString,
+    args: Vec<std::ffi::OsString>,
+}
+
+impl Command {
+    pub fn new<I, S>(program: impl Into<std::ffi::OsString>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<std::ffi::OsString>,
+    {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Canonicalizer for Command {
+    fn canonicalize(&self, content: &mut dyn std::io::Read) -> std::io::Result<Vec<u8>> {
+        let mut input = Vec::new();
+        content.read_to_end(&mut input)?;
+
+        let mut child = std::process::Command::new(&self.program)
+            .args(&self.args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::inherit())
+            .spawn()?;
+
+        let Some(mut stdin) = child.stdin.take() else {
+            return Err(std::io::Error::other("stdin was not captured"));
+        };
+
+        // Feed the content from another thread so that a command that writes before it has read
+        // everything cannot deadlock on a full pipe
+        let output = std::thread::scope(|scope| {
+            let feeder = scope.spawn(move || {
+                use std::io::Write;
+
+                let result = stdin.write_all(&input);
+                drop(stdin);
+                result
+            });
+
+            let output = child.wait_with_output();
+            match feeder.join() {
+                Ok(Ok(())) => output,
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => output,
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err(std::io::Error::other("content feeder panicked")),
+            }
+        })?;
+
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(std::io::Error::other(format!(
+                "`{}` exited with {}",
+                self.program.display(),
+                output.status
+            )))
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use super::{algorithm, entry, options};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not read checksums: {0}")]
+    Read(std::io::Error),
+    #[error("Could not write checksums: {0}")]
+    Write(std::io::Error),
+    #[error("Malformed checksum on line {0}")]
+    Malformed(usize),
+    #[error("Checksum on line {0} uses an unsupported algorithm")]
+    Unsupported(usize),
+    #[error("Checksum on line {0} does not use the same algorithm as the previous ones")]
+    Mixed(usize),
+    #[error("Duplicate checksum for `{0}`")]
+    Duplicate(std::path::PathBuf),
+}
+
+/// Builds a [`Directory`](entry::Directory) rooted at `path` from a coreutils-style checksum
+/// listing, as written by `md5sum` or `sha256sum`
+///
+/// Both the default `HASH  PATH` lines and the BSD-style `ALGORITHM (PATH) = HASH` lines are
+/// understood, the algorithm being inferred from the length of the hashes. Listings carry no
+/// sizes nor kinds, so every entry is a file with a size of zero, and type changes are not looked
+/// for when linking
+///
+/// # Errors
+///
+/// If the listing cannot be read, a line is malformed or uses an unsupported or mixed algorithm,
+/// or a path is listed twice
+pub fn read_checksums(
+    reader: impl std::io::BufRead,
+    path: std::path::PathBuf,
+) -> Result<entry::Directory, super::Error> {
+    let mut algorithm = None;
+    let mut entries = Vec::new();
+    let mut directories = std::collections::BTreeSet::new();
+
+    for (number, line) in reader.lines().enumerate() {
+        let number = number + 1;
+        let line = line.map_err(Error::Read)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (hash, name) = parse(&line).ok_or(Error::Malformed(number))?;
+        let hash = hash.parse::<entry::Hash>().map_err(|e| match e {
+            entry::ParseHashError::Digits(_) => Error::Malformed(number),
+            entry::ParseHashError::Length(_) => Error::Unsupported(number),
+        })?;
+        let listed = algorithm::Algorithm::from_length(hash.as_bytes().len())
+            .ok_or(Error::Unsupported(number))?;
+        if *algorithm.get_or_insert(listed) != listed {
+            return Err(Error::Mixed(number).into());
+        }
+
+        let name = std::path::PathBuf::from(name.strip_prefix("./").unwrap_or(&name));
+        directories.extend(
+            name.ancestors()
+                .skip(1)
+                .filter(|a| !a.as_os_str().is_empty())
+                .map(std::path::Path::to_path_buf),
+        );
+        entries.push(entry::Entry::new(
+            name,
+            hash,
+            None,
+            entry::Kind::File,
+            0,
+            None,
+            None,
+        ));
+    }
+
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].path == pair[1].path) {
+        return Err(Error::Duplicate(pair[0].path.clone()).into());
+    }
+    entries.sort_unstable();
+
+    let settings = options::Settings {
+        algorithm: algorithm.unwrap_or_default(),
+        ..options::Settings::default()
+    };
+    let mut directory =
+        entry::Directory::new(path, entries, directories.into_iter().collect(), settings);
+    directory.live = false;
+    directory.untyped = true;
+    Ok(directory)
+}
+
+/// Writes the raw hash of every entry of the `directory` as a coreutils-style checksum listing,
+/// sorted by path, which `md5sum -c` or `sha256sum -c` can check from within the directory
+///
+/// # Errors
+///
+/// If the listing cannot be written
+pub fn write_checksums(
+    directory: &entry::Directory,
+    mut writer: impl std::io::Write,
+) -> Result<(), super::Error> {
+    let mut entries = directory.entries().iter().collect::<Vec<_>>();
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+    for entry in entries {
+        let name = entry.path.to_string_lossy();
+        // Same escaping as coreutils, a leading backslash marks an escaped name
+        if name.contains(['\\', '\n', '\r']) {
+            let name = name
+                .replace('\\', "\\\\")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            writeln!(writer, "\\{:x}  {name}", entry.raw_hash())
+        } else {
+            writeln!(writer, "{:x}  {name}", entry.raw_hash())
+        }
+        .map_err(Error::Write)?;
+    }
+
+    writer.flush().map_err(Error::Write)?;
+    Ok(())
+}
+
+/// Splits a line into its hash and unescaped path
+fn parse(line: &str) -> Option<(&str, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+
+    let (hash, name) = if let Some((_, rest)) = line.split_once(" (")
+        && let Some((name, hash)) = rest.rsplit_once(") = ")
+    {
+        (hash, name)
+    } else {
+        let (hash, name) = line.split_once(' ')?;
+        // The second separator is a space in text mode and an asterisk in binary mode
+        (hash, name.strip_prefix([' ', '*'])?)
+    };
+
+    if !escaped {
+        return Some((hash, String::from(name)));
+    }
+
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => unescaped.push('\n'),
+            'r' => unescaped.push('\r'),
+            '\\' => unescaped.push('\\'),
+            _ => return None,
+        }
+    }
+    Some((hash, unescaped))
+}
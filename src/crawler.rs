@@ -1,4 +1,4 @@
-use super::entry;
+use super::{algorithm, cache, entry, options, source};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -12,18 +12,33 @@ pub enum Error {
     FullCollision(std::path::PathBuf),
 }
 
-pub fn crawl(path: &std::path::Path, pool: &rayon::ThreadPool) -> Result<Vec<entry::Entry>, Error> {
+/// Indexes the tree at `path` of the file system `fs`, returning its entries sorted by hash and
+/// its directories sorted by path, both relative to `path`
+///
+/// `progress` is called with every entry as soon as it is hashed
+pub fn crawl<F: source::FileSystem>(
+    fs: &std::sync::Arc<F>,
+    path: &std::path::Path,
+    options: &options::Options,
+    cache: Option<&std::sync::Arc<cache::Cache>>,
+    progress: Option<&(dyn Fn(&entry::Entry) + Sync)>,
+    pool: &rayon::ThreadPool,
+) -> Result<(Vec<entry::Entry>, Vec<std::path::PathBuf>), Error> {
     log::info!("Indexing {}", path.display());
     let start = std::time::Instant::now();
 
-    let entries = pool.install(|| {
+    // Accumulating outside of the pool leaves all of its threads to the workers, a pool of a
+    // single thread would otherwise never get to run them
+    let receiver = pool.install(|| {
         let (sender, receiver) = std::sync::mpsc::channel();
 
         let path_clone = path.to_path_buf();
-        worker::scanner::scan(path_clone, sender);
+        let options = std::sync::Arc::new(options.clone());
+        worker::scanner::scan(fs.clone(), path_clone, options, cache.cloned(), sender);
 
-        accumulate(&receiver, path)
-    })?;
+        receiver
+    });
+    let (entries, directories) = accumulate(&receiver, path, progress)?;
 
     if let Some(first) = entries.first() {
         assert!(
@@ -39,6 +54,10 @@ pub fn crawl(path: &std::path::Path, pool: &rayon::ThreadPool) -> Result<Vec<ent
         );
     }
 
+    if let Some(cache) = cache {
+        cache.prune(path, entries.iter().map(entry::Entry::path));
+    }
+
     log::info!(
         "Finished indexing {} items for {} in {:?}",
         entries.len(),
@@ -46,21 +65,74 @@ pub fn crawl(path: &std::path::Path, pool: &rayon::ThreadPool) -> Result<Vec<ent
         start.elapsed(),
     );
 
-    Ok(entries)
+    Ok((entries, directories))
+}
+
+/// Hashes only the first `length` bytes of the file at `path`
+pub fn hash_prefix(
+    path: &std::path::Path,
+    length: u64,
+    algorithm: algorithm::Algorithm,
+) -> Result<entry::Hash, Error> {
+    worker::hash_prefix(path, length, algorithm).map_err(Error::from)
+}
+
+/// Hashes the content read from `reader` the way the file at the relative `path` of a crawled
+/// directory would be, returning the hash, the raw hash if it was transformed, and the raw size
+pub fn hash_reader(
+    reader: impl std::io::Read,
+    path: &std::path::Path,
+    options: &options::Options,
+) -> Result<(entry::Hash, Option<entry::Hash>, u64), Error> {
+    worker::hash_reader(reader, path, options).map_err(Error::from)
+}
+
+/// Gives the entries in `links`, keyed by the path of the entry they are a hardlink to, their own
+/// copy of that entry, all sharing one [`Link`](entry::Link) group
+pub fn resolve_links(
+    entries: &mut Vec<entry::Entry>,
+    mut links: std::collections::HashMap<std::path::PathBuf, Vec<std::path::PathBuf>>,
+) {
+    if links.is_empty() {
+        return;
+    }
+
+    log::debug!("Resolving {} hardlink groups", links.len());
+    let mut group = 0;
+    let mut linked = Vec::new();
+    for entry in &mut *entries {
+        let Some(paths) = links.remove(&entry.path) else {
+            continue;
+        };
+        entry.link = Some(entry::Link::new(group, paths.len() + 1));
+        linked.extend(paths.into_iter().map(|path| entry.linked(path)));
+        group += 1;
+    }
+    entries.extend(linked);
+    entries.sort_unstable();
 }
 
 fn accumulate(
     receiver: &std::sync::mpsc::Receiver<worker::Message>,
     base: &std::path::Path,
-) -> Result<Vec<entry::Entry>, Error> {
+    progress: Option<&(dyn Fn(&entry::Entry) + Sync)>,
+) -> Result<(Vec<entry::Entry>, Vec<std::path::PathBuf>), Error> {
+    let strip = |path: std::path::PathBuf| {
+        path.strip_prefix(base)
+            .map(std::path::Path::to_path_buf)
+            .map_err(|_| Error::StripPrefix(base.to_path_buf(), path))
+    };
+
     let mut paths = std::array::from_fn::<Vec<_>, 32, _>(|_| Vec::new());
+    let mut links = std::collections::HashMap::<_, Vec<_>>::new();
+    let mut directories = Vec::new();
     let mut total = 0;
     let mut hashes = 0_u64;
     let mut done = false;
     let start = std::time::Instant::now();
 
     while let Ok(message) = receiver.recv() {
-        let (hash, path) = match message {
+        let item = match message {
             worker::Message::Scanner(worker::ScannerMessage::Error(e)) => return Err(e.into()),
             worker::Message::Hasher(worker::HasherMessage::Error(e)) => return Err(e.into()),
             worker::Message::Scanner(worker::ScannerMessage::Queued) => {
@@ -71,19 +143,44 @@ fn accumulate(
                 done = true;
                 continue;
             }
-            worker::Message::Hasher(worker::HasherMessage::Hash(hash, path)) => (hash, path),
+            worker::Message::Scanner(worker::ScannerMessage::Directory(path)) => {
+                directories.push(strip(path)?);
+                continue;
+            }
+            worker::Message::Scanner(worker::ScannerMessage::Linked(path, original)) => {
+                links
+                    .entry(strip(original)?)
+                    .or_default()
+                    .push(strip(path)?);
+                continue;
+            }
+            worker::Message::Hasher(worker::HasherMessage::Hash(item)) => item,
         };
 
-        let bucket = usize::from(hash.first_byte() >> 3);
+        let bucket = usize::from(item.hash.first_byte() >> 3);
         let bucket = unsafe { paths.get_unchecked_mut(bucket) };
 
-        let entry = entry::Entry::new(&path, base, hash)
-            .map_err(|_| Error::StripPrefix(base.to_path_buf(), path))?;
+        let mut entry = entry::Entry::new(
+            strip(item.path)?,
+            item.hash,
+            item.raw,
+            item.kind,
+            item.size,
+            item.modified,
+            item.attributes,
+        );
+        entry.mode = item.mode;
+        entry.uid = item.uid;
+        entry.gid = item.gid;
+        entry.member = item.member;
 
         let Err(index) = bucket.binary_search(&entry) else {
             return Err(Error::FullCollision(entry.path));
         };
 
+        if let Some(progress) = progress {
+            progress(&entry);
+        }
         bucket.insert(index, entry);
         hashes += 1;
 
@@ -99,12 +196,59 @@ fn accumulate(
         }
     }
 
-    Ok(paths.into_iter().flatten().collect())
+    let mut entries = paths.into_iter().flatten().collect::<Vec<_>>();
+    resolve_links(&mut entries, links);
+
+    directories.sort_unstable();
+
+    Ok((entries, directories))
 }
 
 mod worker {
-    use crate::entry::Hash;
-    pub use hasher::{Error as HasherError, Message as HasherMessage};
+    use crate::algorithm::Algorithm;
+    use crate::cache::Cache;
+    use crate::canonicalize::Canonicalizer;
+    use crate::decompress::{self, Compression};
+    use crate::entry::{Attributes, Hash, Kind};
+    use crate::normalize::{Normalizer, Rules};
+    use crate::options::Options;
+    use crate::source::FileSystem;
+
+    /// How the content of a file is transformed before hashing
+    pub enum Transform {
+        Canonicalize(std::sync::Arc<dyn Canonicalizer>),
+        Normalize(Rules),
+    }
+
+    impl Transform {
+        /// Identifies the transform in the [`Cache`], so hashes are not reused across transforms
+        fn describe(&self) -> String {
+            match self {
+                Transform::Canonicalize(canonicalizer) => format!("{canonicalizer:?}"),
+                Transform::Normalize(rules) => format!("{rules:?}"),
+            }
+        }
+    }
+    /// Finds how the content of the file at the relative `path` is transformed before hashing,
+    /// matching compressed files by the name of their decompressed content
+    fn transform(path: &std::path::Path, options: &Options) -> Option<Transform> {
+        let decompressed = decompress::find(path, &options.decompressions).map(|(_, path)| path);
+        let path = decompressed.as_deref().unwrap_or(path);
+        options
+            .canonicalizations
+            .iter()
+            .find(|canonicalization| canonicalization.glob.matches_path(path))
+            .map(|canonicalization| Transform::Canonicalize(canonicalization.canonicalizer.clone()))
+            .or_else(|| {
+                options
+                    .normalizations
+                    .iter()
+                    .find(|normalization| normalization.glob.matches_path(path))
+                    .map(|normalization| Transform::Normalize(normalization.rules))
+            })
+    }
+
+    pub use hasher::{Error as HasherError, Message as HasherMessage, hash_prefix, hash_reader};
     pub use scanner::{Error as ScannerError, Message as ScannerMessage};
 
     pub enum Message {
@@ -125,10 +269,12 @@ mod worker {
     }
 
     pub mod scanner {
-        use super::Message as WorkerMessage;
+        use super::{Cache, FileSystem, Kind, Message as WorkerMessage, Options, Transform};
 
         pub enum Message {
             Queued,
+            Directory(std::path::PathBuf),
+            Linked(std::path::PathBuf, std::path::PathBuf),
             Done,
             Error(Error),
         }
@@ -137,15 +283,28 @@ mod worker {
         pub enum Error {
             #[error("Could not read directory `{0}`: {1}")]
             DirUnreadable(std::path::PathBuf, std::io::Error),
-            #[error("Could not read directory entry for `{0}`: {1}")]
-            EntryUnreadable(std::path::PathBuf, std::io::Error),
             #[error("Failed to send queue signal")]
             Send,
         }
 
-        pub fn scan(path: std::path::PathBuf, sender: std::sync::mpsc::Sender<WorkerMessage>) {
+        pub fn scan<F: FileSystem>(
+            fs: std::sync::Arc<F>,
+            path: std::path::PathBuf,
+            options: std::sync::Arc<Options>,
+            cache: Option<std::sync::Arc<Cache>>,
+            sender: std::sync::mpsc::Sender<WorkerMessage>,
+        ) {
             rayon::spawn(move || {
-                if let Err(e) = scan_internal(path, sender.clone()) {
+                let mut inodes = std::collections::HashMap::new();
+                if let Err(e) = scan_internal(
+                    &fs,
+                    path.clone(),
+                    &path,
+                    &options,
+                    cache.as_ref(),
+                    &mut inodes,
+                    sender.clone(),
+                ) {
                     log::warn!("Failed to send error from scanner: {e}");
                 }
                 if let Err(e) = sender.send(Message::Done.into()) {
@@ -154,11 +313,19 @@ mod worker {
             });
         }
 
-        fn scan_internal(
+        fn scan_internal<F: FileSystem>(
+            fs: &std::sync::Arc<F>,
             path: std::path::PathBuf,
+            base: &std::path::Path,
+            options: &std::sync::Arc<Options>,
+            cache: Option<&std::sync::Arc<Cache>>,
+            inodes: &mut std::collections::HashMap<(u64, u64), std::path::PathBuf>,
             sender: std::sync::mpsc::Sender<WorkerMessage>,
         ) -> Result<(), Error> {
-            let dir = match scan_dir(&path) {
+            let dir = match fs
+                .read_dir(&path)
+                .map_err(|e| Error::DirUnreadable(path.clone(), e))
+            {
                 Ok(dir) => dir,
                 Err(e) => {
                     // Always quit on error.
@@ -179,20 +346,42 @@ mod worker {
             };
 
             for path in dir {
-                let is_dir = if path.is_symlink() {
-                    if let Ok(meta) = path.metadata() {
-                        meta.is_dir()
+                let Ok(metadata) = fs.symlink_metadata(&path) else {
+                    log::warn!("Could not read metadata of {}", path.display());
+                    continue;
+                };
+                let is_symlink = metadata.kind == Kind::Symlink;
+                let is_dir = if is_symlink {
+                    if let Ok(meta) = fs.metadata(&path) {
+                        meta.kind == Kind::Directory
                     } else {
                         log::warn!("Found broken symlink at {}", path.display());
                         continue;
                     }
                 } else {
-                    path.is_dir()
+                    metadata.kind == Kind::Directory
                 };
 
                 let sender = sender.clone();
                 if is_dir {
-                    scan_internal(path, sender)?;
+                    sender
+                        .send(Message::Directory(path.clone()).into())
+                        .map_err(|e| match e.0 {
+                            WorkerMessage::Scanner(Message::Directory(_)) => Error::Send,
+                            _ => unreachable!(
+                                "Cannot fail to send anything other than a scanner::Message::Directory"
+                            ),
+                        })?;
+                    scan_internal(fs, path, base, options, cache, inodes, sender)?;
+                } else if let Some(original) = original(&metadata, &path, inodes) {
+                    sender
+                        .send(Message::Linked(path, original).into())
+                        .map_err(|e| match e.0 {
+                            WorkerMessage::Scanner(Message::Linked(..)) => Error::Send,
+                            _ => unreachable!(
+                                "Cannot fail to send anything other than a scanner::Message::Linked"
+                            ),
+                        })?;
                 } else {
                     sender.send(Message::Queued.into()).map_err(|e| match e.0 {
                         WorkerMessage::Scanner(Message::Queued) => Error::Send,
@@ -200,7 +389,41 @@ mod worker {
                             "Cannot fail to send anything other than a scanner::Message::Queue"
                         ),
                     })?;
-                    rayon::spawn(move || super::hasher::hash(path, sender));
+                    let kind = if is_symlink {
+                        Kind::Symlink
+                    } else {
+                        Kind::File
+                    };
+                    let transform = transform(&path, base, options);
+                    let fs = fs.clone();
+                    let options = options.clone();
+                    let cache = cache.cloned();
+                    if options.into_archives && crate::archive::has_archive_name(&path) {
+                        rayon::spawn(move || {
+                            super::hasher::expand(
+                                fs.as_ref(),
+                                path,
+                                kind,
+                                transform,
+                                &options,
+                                cache.as_deref(),
+                                sender,
+                            );
+                        });
+                        continue;
+                    }
+
+                    rayon::spawn(move || {
+                        super::hasher::hash(
+                            fs.as_ref(),
+                            path,
+                            kind,
+                            transform,
+                            &options,
+                            cache.as_deref(),
+                            sender,
+                        );
+                    });
                 }
             }
 
@@ -209,35 +432,81 @@ mod worker {
             Ok(())
         }
 
-        fn scan_dir(path: &std::path::Path) -> Result<Vec<std::path::PathBuf>, Error> {
-            path.read_dir()
-                .map_err(|e| Error::DirUnreadable(path.to_path_buf(), e))?
-                .map(|entry| entry.map(|p| p.path()))
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(|e| Error::EntryUnreadable(path.to_path_buf(), e))
+        /// Returns the first path seen with the inode of a file that has several hardlinks,
+        /// remembering `path` if it is the first
+        fn original(
+            metadata: &crate::source::Metadata,
+            path: &std::path::Path,
+            inodes: &mut std::collections::HashMap<(u64, u64), std::path::PathBuf>,
+        ) -> Option<std::path::PathBuf> {
+            let inode = metadata.inode.filter(|_| metadata.links > 1)?;
+            match inodes.entry(inode) {
+                std::collections::hash_map::Entry::Occupied(original) => {
+                    Some(original.get().clone())
+                }
+                std::collections::hash_map::Entry::Vacant(vacant) => {
+                    vacant.insert(path.to_path_buf());
+                    None
+                }
+            }
+        }
+
+        fn transform(
+            path: &std::path::Path,
+            base: &std::path::Path,
+            options: &Options,
+        ) -> Option<Transform> {
+            super::transform(path.strip_prefix(base).ok()?, options)
         }
     }
 
     mod hasher {
-        use super::{Hash, Message as WorkerMessage};
+        use super::{
+            Algorithm, Attributes, Cache, Canonicalizer, Compression, FileSystem, Hash, Kind,
+            Message as WorkerMessage, Normalizer, Options, Rules, Transform, decompress,
+        };
 
         pub enum Message {
-            Hash(Hash, std::path::PathBuf),
+            Hash(Hashed),
             Error(Error),
         }
 
+        pub struct Hashed {
+            pub hash: Hash,
+            pub raw: Option<Hash>,
+            pub path: std::path::PathBuf,
+            pub kind: Kind,
+            pub size: u64,
+            pub modified: Option<std::time::SystemTime>,
+            pub mode: Option<u32>,
+            pub uid: Option<u32>,
+            pub gid: Option<u32>,
+            pub attributes: Option<Attributes>,
+            pub member: bool,
+        }
+
         #[derive(Debug, thiserror::Error)]
         pub enum Error {
             #[error("Could not open file {0}: {1}")]
             CannotOpen(std::path::PathBuf, std::io::Error),
             #[error("Could not read file {0}: {1}")]
             CannotRead(std::path::PathBuf, std::io::Error),
+            #[error("Could not read extended attributes of {0}: {1}")]
+            CannotReadAttributes(std::path::PathBuf, std::io::Error),
             #[error("Could not send entry: {0}")]
             Send(std::path::PathBuf),
         }
 
-        pub fn hash(path: std::path::PathBuf, sender: std::sync::mpsc::Sender<WorkerMessage>) {
-            if let Err(e) = hash_internal(path, sender) {
+        pub fn hash<F: FileSystem>(
+            fs: &F,
+            path: std::path::PathBuf,
+            kind: Kind,
+            transform: Option<Transform>,
+            options: &Options,
+            cache: Option<&Cache>,
+            sender: std::sync::mpsc::Sender<WorkerMessage>,
+        ) {
+            if let Err(e) = hash_internal(fs, path, kind, transform, options, cache, sender) {
                 match e {
                     Error::Send(path) => {
                         log::warn!("Failed to send entry from hasher: {}", path.display());
@@ -247,14 +516,21 @@ mod worker {
             }
         }
 
-        fn hash_internal(
+        fn hash_internal<F: FileSystem>(
+            fs: &F,
             path: std::path::PathBuf,
+            kind: Kind,
+            transform: Option<Transform>,
+            options: &Options,
+            cache: Option<&Cache>,
             sender: std::sync::mpsc::Sender<WorkerMessage>,
         ) -> Result<(), Error> {
             macro_rules! send {
                 ($value: expr) => {
                     sender.send($value.into()).map_err(|e| match e.0 {
-                        WorkerMessage::Hasher(Message::Hash(_, path)) => Error::Send(path),
+                        WorkerMessage::Hasher(Message::Hash(Hashed { path, .. })) => {
+                            Error::Send(path)
+                        }
                         WorkerMessage::Hasher(Message::Error(e)) => e,
                         WorkerMessage::Scanner(_) => {
                             unreachable!(
@@ -274,39 +550,335 @@ mod worker {
                 };
             }
 
-            use md5::Digest;
-
-            let mut file = unwrap!(
-                std::fs::OpenOptions::new()
-                    .read(true)
-                    .write(false)
-                    .create(false)
-                    .open(&path)
+            // Taken before reading, so a write racing the hasher makes the file look modified
+            let metadata = unwrap!(
+                fs.metadata(&path)
                     .map_err(|e| Error::CannotOpen(path.clone(), e))
             );
+            let file = unwrap!(
+                fs.open(&path)
+                    .map_err(|e| Error::CannotOpen(path.clone(), e))
+            );
+            let modified = metadata.modified;
+            // Taken through symlinks like the content, those of the symlink itself say nothing
+            let (mode, uid, gid) = if kind == Kind::Symlink {
+                (None, None, None)
+            } else {
+                (metadata.mode, metadata.uid, metadata.gid)
+            };
+            let compression = decompress::find(&path, &options.decompressions)
+                .map(|(decompression, _)| decompression.format);
+            let mut description = match &transform {
+                Some(transform) => format!("{:?} {}", options.algorithm, transform.describe()),
+                None => format!("{:?}", options.algorithm),
+            };
+            if let Some(compression) = compression {
+                description = format!("{description} {compression}");
+            }
+
+            let cached = cache.and_then(|cache| cache.get(&path, &metadata, &description));
+            let (hash, raw, size) = if let Some(cached) = cached {
+                cached
+            } else {
+                let hashed = unwrap!(match compression {
+                    Some(compression) => {
+                        decompressed(file, &path, compression, transform, options.algorithm)
+                    }
+                    None => content(file, &path, transform, options.algorithm),
+                });
+                if let Some(cache) = cache {
+                    cache.insert(path.clone(), &metadata, description, hashed.clone());
+                }
+                hashed
+            };
+
+            let attributes = if options.attributes {
+                Some(unwrap!(match fs.attributes(&path) {
+                    // A file system without extended attributes holds none
+                    Err(e) if is_unsupported(&e) => Ok(Attributes::default()),
+                    attributes => {
+                        attributes.map_err(|e| Error::CannotReadAttributes(path.clone(), e))
+                    }
+                }))
+            } else {
+                None
+            };
+
+            send!(Message::Hash(Hashed {
+                hash,
+                raw,
+                path,
+                kind,
+                size,
+                modified,
+                mode,
+                uid,
+                gid,
+                attributes,
+                member: false,
+            }))?;
+
+            drop(sender);
+            Ok(())
+        }
+
+        /// Sends the members of the archive at `path` in place of the archive itself, or hashes
+        /// it as a file if it cannot be read as an archive
+        pub fn expand<F: FileSystem>(
+            fs: &F,
+            path: std::path::PathBuf,
+            kind: Kind,
+            transform: Option<Transform>,
+            options: &Options,
+            cache: Option<&Cache>,
+            sender: std::sync::mpsc::Sender<WorkerMessage>,
+        ) {
+            let members = match fs.open(&path) {
+                Ok(file) => crate::archive::expand(file, &path, options),
+                Err(e) => {
+                    let e = Error::CannotOpen(path.clone(), e);
+                    if sender.send(Message::Error(e).into()).is_err() {
+                        log::warn!("Failed to send error from hasher: {}", path.display());
+                    }
+                    return;
+                }
+            };
+            let (entries, directories) = match members {
+                Ok(members) => members,
+                Err(e) => {
+                    log::warn!("Hashing archive {} as a file: {e}", path.display());
+                    hash(fs, path, kind, transform, options, cache, sender);
+                    return;
+                }
+            };
+
+            let directories = std::iter::once(path.clone()).chain(
+                directories
+                    .into_iter()
+                    .map(|directory| path.join(directory)),
+            );
+            for directory in directories {
+                if sender
+                    .send(super::scanner::Message::Directory(directory).into())
+                    .is_err()
+                {
+                    log::warn!("Failed to send directory from hasher: {}", path.display());
+                    return;
+                }
+            }
+
+            for entry in entries {
+                let hashed = Hashed {
+                    hash: entry.hash,
+                    raw: entry.raw,
+                    path: path.join(entry.path),
+                    kind: entry.kind,
+                    size: entry.size,
+                    modified: entry.modified,
+                    mode: entry.mode,
+                    uid: entry.uid,
+                    gid: entry.gid,
+                    attributes: entry.attributes,
+                    member: true,
+                };
+                if sender.send(Message::Hash(hashed).into()).is_err() {
+                    log::warn!("Failed to send entry from hasher: {}", path.display());
+                    return;
+                }
+            }
+        }
+
+        pub fn hash_prefix(
+            path: &std::path::Path,
+            length: u64,
+            algorithm: Algorithm,
+        ) -> Result<Hash, Error> {
+            use std::io::Read;
+
+            let file =
+                std::fs::File::open(path).map_err(|e| Error::CannotOpen(path.to_path_buf(), e))?;
+            digest(file.take(length), path, algorithm, None).map(|(hash, ..)| hash)
+        }
+
+        pub fn hash_reader(
+            reader: impl std::io::Read,
+            path: &std::path::Path,
+            options: &Options,
+        ) -> Result<(Hash, Option<Hash>, u64), Error> {
+            let transform = super::transform(path, options);
+            match decompress::find(path, &options.decompressions) {
+                Some((decompression, _)) => decompressed(
+                    reader,
+                    path,
+                    decompression.format,
+                    transform,
+                    options.algorithm,
+                ),
+                None => content(reader, path, transform, options.algorithm),
+            }
+        }
 
-            let mut hasher = md5::Md5::new();
+        fn content(
+            reader: impl std::io::Read,
+            path: &std::path::Path,
+            transform: Option<Transform>,
+            algorithm: Algorithm,
+        ) -> Result<(Hash, Option<Hash>, u64), Error> {
+            match transform {
+                Some(Transform::Canonicalize(canonicalizer)) => {
+                    canonical(reader, path, algorithm, canonicalizer.as_ref())
+                }
+                Some(Transform::Normalize(rules)) => digest(reader, path, algorithm, Some(rules)),
+                None => digest(reader, path, algorithm, None),
+            }
+        }
+
+        /// Whether `error` tells that the file system does not support extended attributes
+        fn is_unsupported(error: &std::io::Error) -> bool {
+            // Both are the same on Linux, but not on every unix
+            #[cfg(unix)]
+            if error
+                .raw_os_error()
+                .is_some_and(|code| code == libc::ENOTSUP || code == libc::EOPNOTSUPP)
+            {
+                return true;
+            }
+            error.kind() == std::io::ErrorKind::Unsupported
+        }
+
+        /// Same as [`content`], but of the decompressed content, while the raw hash and size
+        /// remain those of the compressed bytes
+        ///
+        /// Files that cannot be decompressed are hashed as they are, with a warning
+        fn decompressed(
+            reader: impl std::io::Read,
+            path: &std::path::Path,
+            compression: Compression,
+            transform: Option<Transform>,
+            algorithm: Algorithm,
+        ) -> Result<(Hash, Option<Hash>, u64), Error> {
+            let mut raw = Raw {
+                reader,
+                hasher: algorithm.hasher(),
+                size: 0,
+                failed: false,
+            };
+            let decoded = match compression.decoder(&mut raw) {
+                Ok(decoder) => content(decoder, path, transform, algorithm),
+                Err(e) => Err(Error::CannotRead(path.to_path_buf(), e)),
+            };
+            let hash = match decoded {
+                Ok((hash, ..)) => Some(hash),
+                Err(Error::CannotRead(_, e)) if !raw.failed => {
+                    log::warn!(
+                        "Could not decompress file {}, hashing it as is: {e}",
+                        path.display()
+                    );
+                    None
+                }
+                Err(e) => return Err(e),
+            };
+            // Whatever follows the compressed stream is still part of the file
+            std::io::copy(&mut raw, &mut std::io::sink())
+                .map_err(|e| Error::CannotRead(path.to_path_buf(), e))?;
+
+            let raw_hash = raw.hasher.finalize();
+            Ok(match hash {
+                Some(hash) => (hash, Some(raw_hash), raw.size),
+                None => (raw_hash, None, raw.size),
+            })
+        }
+
+        /// Hashes and counts the bytes read through it, remembering whether reading failed
+        struct Raw<R> {
+            reader: R,
+            hasher: crate::algorithm::Hasher,
+            size: u64,
+            failed: bool,
+        }
+
+        impl<R: std::io::Read> std::io::Read for Raw<R> {
+            fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+                let bytes = self
+                    .reader
+                    .read(buffer)
+                    .inspect_err(|_| self.failed = true)?;
+                self.hasher.update(&buffer[..bytes]);
+                self.size += bytes as u64;
+                Ok(bytes)
+            }
+        }
+
+        /// Returns the hash of the content, the raw hash if normalization altered the content,
+        /// and the raw size
+        fn digest(
+            mut reader: impl std::io::Read,
+            path: &std::path::Path,
+            algorithm: Algorithm,
+            rules: Option<Rules>,
+        ) -> Result<(Hash, Option<Hash>, u64), Error> {
+            let mut hasher = algorithm.hasher();
+            let mut normalized = rules.map(|rules| (Normalizer::new(rules), algorithm.hasher()));
             let mut buffer = [0; 1024 * 4];
+            let mut size = 0;
 
             loop {
-                use std::io::Read;
-
-                let bytes = unwrap!(
-                    file.read(&mut buffer)
-                        .map_err(|e| Error::CannotRead(path.clone(), e))
-                );
+                let bytes = reader
+                    .read(&mut buffer)
+                    .map_err(|e| Error::CannotRead(path.to_path_buf(), e))?;
 
                 if bytes == 0 {
                     break;
                 }
 
                 hasher.update(&buffer[..bytes]);
+                if let Some((normalizer, hasher)) = &mut normalized {
+                    normalizer.update(&buffer[..bytes], &mut |bytes| hasher.update(bytes));
+                }
+                size += bytes as u64;
             }
 
-            send!(Message::Hash(super::Hash::new(hasher.finalize()), path))?;
+            let hash = hasher.finalize();
+            if let Some((normalizer, mut normalized)) = normalized
+                && normalizer.finish(&mut |bytes| normalized.update(bytes))
+            {
+                Ok((normalized.finalize(), Some(hash), size))
+            } else {
+                Ok((hash, None, size))
+            }
+        }
 
-            drop(sender);
-            Ok(())
+        /// Same as [`digest`], but buffers the whole content for the [`Canonicalizer`], falling back
+        /// to the content itself if it fails
+        fn canonical(
+            mut reader: impl std::io::Read,
+            path: &std::path::Path,
+            algorithm: Algorithm,
+            canonicalizer: &dyn Canonicalizer,
+        ) -> Result<(Hash, Option<Hash>, u64), Error> {
+            let mut content = Vec::new();
+            let size = reader
+                .read_to_end(&mut content)
+                .map_err(|e| Error::CannotRead(path.to_path_buf(), e))?;
+
+            let hash = algorithm.digest(&content);
+            // Files the canonicalizer cannot make sense of are still compared by their content
+            let canonical = match canonicalizer.canonicalize(&mut content.as_slice()) {
+                Ok(canonical) => canonical,
+                Err(e) => {
+                    log::warn!(
+                        "Could not canonicalize file {}, hashing its content instead: {e}",
+                        path.display()
+                    );
+                    return Ok((hash, None, size as u64));
+                }
+            };
+
+            if canonical == content {
+                Ok((hash, None, size as u64))
+            } else {
+                Ok((algorithm.digest(&canonical), Some(hash), size as u64))
+            }
         }
     }
 }
@@ -0,0 +1,95 @@
+/// Hashes the decompressed content of the files whose name ends with `.`[`extension`](Self::extension)
+/// instead of their compressed bytes, so they can be linked to uncompressed copies
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Decompression {
+    /// The extension, without its leading dot and matched regardless of case, e.g. `gz` or
+    /// `warc.gz`, which is removed from the name of the decompressed file
+    pub extension: String,
+    pub format: Compression,
+}
+
+/// The compression formats a [`Decompression`] can undo
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Guesses the format from its conventional extension, `gz`, `zst` or `xz`
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Self::Gzip),
+            "zst" | "zstd" => Some(Self::Zstd),
+            "xz" => Some(Self::Xz),
+            _ => None,
+        }
+    }
+
+    /// Wraps `reader` so that it yields the decompressed content, concatenated streams included
+    pub(crate) fn decoder<'a>(
+        self,
+        reader: impl std::io::Read + 'a,
+    ) -> std::io::Result<Box<dyn std::io::Read + 'a>> {
+        Ok(match self {
+            Self::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Self::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            Self::Xz => Box::new(liblzma::read::XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gzip => f.write_str("gzip"),
+            Self::Zstd => f.write_str("zstd"),
+            Self::Xz => f.write_str("xz"),
+        }
+    }
+}
+
+impl Decompression {
+    /// Decompresses `.gz`, `.zst` and `.xz` files
+    #[must_use]
+    pub fn defaults() -> Vec<Self> {
+        [
+            ("gz", Compression::Gzip),
+            ("zst", Compression::Zstd),
+            ("xz", Compression::Xz),
+        ]
+        .into_iter()
+        .map(|(extension, format)| Self {
+            extension: String::from(extension),
+            format,
+        })
+        .collect()
+    }
+
+    /// Returns `path` without the extension if it has it, i.e. the name of the decompressed file
+    fn strip(&self, path: &std::path::Path) -> Option<std::path::PathBuf> {
+        let name = path.file_name()?.as_encoded_bytes();
+        let extension = self.extension.as_bytes();
+        let stem = name.len().checked_sub(extension.len() + 1)?;
+        if stem == 0 || name[stem] != b'.' || !name[stem + 1..].eq_ignore_ascii_case(extension) {
+            return None;
+        }
+
+        // The stem ends right before an ASCII dot, which is a valid boundary to split at
+        let stem = unsafe { std::ffi::OsStr::from_encoded_bytes_unchecked(&name[..stem]) };
+        Some(path.with_file_name(stem))
+    }
+}
+
+/// Finds the first [`Decompression`] that applies to `path`, with the path of the decompressed
+/// file
+pub(crate) fn find<'a>(
+    path: &std::path::Path,
+    decompressions: &'a [Decompression],
+) -> Option<(&'a Decompression, std::path::PathBuf)> {
+    decompressions
+        .iter()
+        .find_map(|decompression| Some((decompression, decompression.strip(path)?)))
+}
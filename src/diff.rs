@@ -0,0 +1,121 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not open file {0}: {1}")]
+    CannotOpen(std::path::PathBuf, std::io::Error),
+    #[error("Could not read file {0}: {1}")]
+    CannotRead(std::path::PathBuf, std::io::Error),
+}
+
+/// The difference between the contents of two files
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Diff {
+    /// A unified diff of two text files
+    Text(String),
+    /// A compact report for files that are not text, or are too large to be diffed as text
+    Binary {
+        /// The first offset at which the contents differ, if any
+        offset: Option<u64>,
+        /// How many bytes differ at the same offsets, plus the difference in length
+        changed: u64,
+    },
+}
+
+/// Compares the contents of the files at `left` and `right`
+///
+/// Files are diffed as text only if both are valid UTF-8 without NUL bytes and neither is larger
+/// than `limit` bytes. Anything else is compared byte-by-byte without loading it into memory
+///
+/// # Errors
+///
+/// If either file cannot be read
+pub fn diff(
+    left: &std::path::Path,
+    right: &std::path::Path,
+    limit: u64,
+) -> Result<Diff, super::Error> {
+    if let (Some(left_text), Some(right_text)) = (text(left, limit)?, text(right, limit)?) {
+        let diff = similar::TextDiff::from_lines(&left_text, &right_text)
+            .unified_diff()
+            .header(&left.display().to_string(), &right.display().to_string())
+            .to_string();
+        return Ok(Diff::Text(diff));
+    }
+
+    binary(left, right).map_err(super::Error::from)
+}
+
+fn text(path: &std::path::Path, limit: u64) -> Result<Option<String>, Error> {
+    use std::io::Read;
+
+    let file = std::fs::File::open(path).map_err(|e| Error::CannotOpen(path.to_path_buf(), e))?;
+
+    let mut bytes = Vec::new();
+    file.take(limit.saturating_add(1))
+        .read_to_end(&mut bytes)
+        .map_err(|e| Error::CannotRead(path.to_path_buf(), e))?;
+
+    if bytes.len() as u64 > limit || bytes.contains(&0) {
+        return Ok(None);
+    }
+
+    Ok(String::from_utf8(bytes).ok())
+}
+
+fn binary(left: &std::path::Path, right: &std::path::Path) -> Result<Diff, Error> {
+    let open = |path: &std::path::Path| {
+        std::fs::File::open(path)
+            .map(std::io::BufReader::new)
+            .map_err(|e| Error::CannotOpen(path.to_path_buf(), e))
+    };
+
+    let mut left_reader = open(left)?;
+    let mut right_reader = open(right)?;
+
+    let mut position = 0;
+    let mut offset = None;
+    let mut changed = 0;
+
+    loop {
+        use std::io::BufRead;
+
+        let left_buffer = left_reader
+            .fill_buf()
+            .map_err(|e| Error::CannotRead(left.to_path_buf(), e))?;
+        let right_buffer = right_reader
+            .fill_buf()
+            .map_err(|e| Error::CannotRead(right.to_path_buf(), e))?;
+
+        let length = left_buffer.len().min(right_buffer.len());
+        if length == 0 {
+            break;
+        }
+
+        for (i, (l, r)) in left_buffer[..length]
+            .iter()
+            .zip(&right_buffer[..length])
+            .enumerate()
+        {
+            if l != r {
+                offset.get_or_insert(position + i as u64);
+                changed += 1;
+            }
+        }
+
+        position += length as u64;
+        left_reader.consume(length);
+        right_reader.consume(length);
+    }
+
+    let remaining = |mut reader: std::io::BufReader<std::fs::File>, path: &std::path::Path| {
+        std::io::copy(&mut reader, &mut std::io::sink())
+            .map_err(|e| Error::CannotRead(path.to_path_buf(), e))
+    };
+
+    let extra = remaining(left_reader, left)? + remaining(right_reader, right)?;
+    if extra > 0 {
+        offset.get_or_insert(position);
+        changed += extra;
+    }
+
+    Ok(Diff::Binary { offset, changed })
+}
@@ -0,0 +1,105 @@
+use super::{cache, crawler, entry, options, source, thread};
+
+/// Files within one directory that share the same content
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Duplicates {
+    hash: entry::Hash,
+    size: u64,
+    paths: Vec<std::path::PathBuf>,
+    wasted: u64,
+}
+
+impl Duplicates {
+    #[must_use]
+    pub fn hash(&self) -> &entry::Hash {
+        &self.hash
+    }
+
+    /// The size of a single copy
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The sorted paths of every copy, relative to the searched directory
+    #[must_use]
+    pub fn paths(&self) -> &[std::path::PathBuf] {
+        &self.paths
+    }
+
+    /// The bytes taken by every copy but one, not counting copies that are hardlinks of each
+    /// other since they already share their storage
+    #[must_use]
+    pub fn wasted(&self) -> u64 {
+        self.wasted
+    }
+}
+
+/// Finds the groups of files with the same content within the directory at `path`, largest
+/// [`wasted`](Duplicates::wasted) first
+///
+/// Empty files are not reported, and the content is compared the same way as in
+/// [`compare`](crate::compare), so normalizations and canonicalizations apply
+///
+/// # Errors
+///
+/// This is a fallible process and will fail-fast, see [`compare`](crate::compare)
+pub fn dupes(
+    path: &std::path::Path,
+    options: &options::Options,
+) -> Result<Vec<Duplicates>, super::Error> {
+    let pool = thread::pool()?;
+    let cache = cache::Cache::open(options);
+    let (entries, _) = crawler::crawl(
+        &std::sync::Arc::new(source::Local),
+        path,
+        options,
+        cache.as_ref(),
+        None,
+        &pool,
+    )?;
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+
+    log::info!("Starting duplicates search");
+    let start = std::time::Instant::now();
+
+    // Entries are sorted by hash, so copies are next to each other
+    let mut groups = entries
+        .chunk_by(|a, b| a.hash == b.hash)
+        .filter(|chunk| chunk.len() > 1)
+        .filter_map(|chunk| {
+            let files = chunk
+                .iter()
+                .filter(|e| e.kind == entry::Kind::File && e.size > 0)
+                .collect::<Vec<_>>();
+            let first = files.first()?;
+
+            let mut groups = files
+                .iter()
+                .filter_map(|e| e.link.map(|l| l.group()))
+                .collect::<Vec<_>>();
+            groups.sort_unstable();
+            groups.dedup();
+            let copies = files.iter().filter(|e| e.link.is_none()).count() + groups.len();
+
+            (files.len() > 1).then(|| Duplicates {
+                hash: first.hash.clone(),
+                size: first.size,
+                paths: files.iter().map(|e| e.path.clone()).collect(),
+                wasted: first.size * (copies as u64 - 1),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    groups.sort_by(|a, b| b.wasted.cmp(&a.wasted).then_with(|| a.paths.cmp(&b.paths)));
+
+    log::info!(
+        "Finished duplicates search with {} groups in {:?}",
+        groups.len(),
+        start.elapsed()
+    );
+
+    Ok(groups)
+}
@@ -1,7 +1,15 @@
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Directory {
     path: std::path::PathBuf,
-    entries: Vec<Entry>,
+    pub(crate) entries: Vec<Entry>,
+    pub(crate) directories: Vec<std::path::PathBuf>,
+    settings: super::Settings,
+    /// Only directories indexed by this process can be read, not the ones loaded from a manifest
+    #[serde(skip)]
+    pub(crate) live: bool,
+    /// Checksum listings only hold files, whatever they were, so their kinds cannot be compared
+    #[serde(skip)]
+    pub(crate) untyped: bool,
 }
 
 impl Directory {
@@ -15,6 +23,18 @@ impl Directory {
         &self.entries
     }
 
+    /// The sorted paths of every directory found under [`path`](Self::path)
+    #[must_use]
+    pub fn directories(&self) -> &[std::path::PathBuf] {
+        &self.directories
+    }
+
+    /// The options this directory was indexed with, as far as they affect its hashes
+    #[must_use]
+    pub fn settings(&self) -> &super::Settings {
+        &self.settings
+    }
+
     #[must_use]
     pub fn decompose(self) -> (std::path::PathBuf, Vec<Entry>) {
         (self.path, self.entries)
@@ -22,16 +42,42 @@ impl Directory {
 }
 
 impl Directory {
-    pub(crate) fn new(path: std::path::PathBuf, entries: Vec<Entry>) -> Self {
-        Self { path, entries }
+    pub(crate) fn new(
+        path: std::path::PathBuf,
+        entries: Vec<Entry>,
+        directories: Vec<std::path::PathBuf>,
+        settings: super::Settings,
+    ) -> Self {
+        Self {
+            path,
+            entries,
+            directories,
+            settings,
+            live: true,
+            untyped: false,
+        }
     }
 }
 
 #[derive(Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Entry {
     pub(crate) hash: Hash,
+    pub(crate) raw: Option<Hash>,
     pub(crate) path: std::path::PathBuf,
+    pub(crate) kind: Kind,
+    pub(crate) size: u64,
+    #[serde(with = "timestamp")]
+    pub(crate) modified: Option<std::time::SystemTime>,
+    pub(crate) mode: Option<u32>,
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
     pub(crate) status: Status,
+    pub(crate) attributes: Option<Attributes>,
+    pub(crate) link: Option<Link>,
+    pub(crate) flags: Flags,
+    /// Held within an archive, so not on disk for the checks that read file contents again
+    #[serde(skip)]
+    pub(crate) member: bool,
 }
 
 impl Entry {
@@ -40,30 +86,119 @@ impl Entry {
         &self.hash
     }
 
+    /// The hash of the content before normalization, which is only different from
+    /// [`hash`](Self::hash) if a [`Normalization`](crate::Normalization) or a
+    /// [`Canonicalization`](crate::Canonicalization) altered the content
+    #[must_use]
+    pub fn raw_hash(&self) -> &Hash {
+        self.raw.as_ref().unwrap_or(&self.hash)
+    }
+
     #[must_use]
     pub fn path(&self) -> &std::path::Path {
         &self.path
     }
 
+    #[must_use]
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The last modification time, if the platform reports it
+    #[must_use]
+    pub fn modified(&self) -> Option<std::time::SystemTime> {
+        self.modified
+    }
+
+    /// The permission bits, along with the setuid, setgid and sticky bits, when known
+    #[must_use]
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    #[must_use]
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    #[must_use]
+    pub fn gid(&self) -> Option<u32> {
+        self.gid
+    }
+
     #[must_use]
     pub fn status(&self) -> &Status {
         &self.status
     }
+
+    #[must_use]
+    pub fn attributes(&self) -> Option<&Attributes> {
+        self.attributes.as_ref()
+    }
+
+    /// The hardlink group this entry belongs to, if other entries in the same [`Directory`] share
+    /// its inode
+    #[must_use]
+    pub fn link(&self) -> Option<&Link> {
+        self.link.as_ref()
+    }
+
+    #[must_use]
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
 }
 
 impl Entry {
     pub(crate) fn new(
-        path: &std::path::Path,
-        base: &std::path::Path,
+        path: std::path::PathBuf,
         hash: Hash,
-    ) -> Result<Self, std::path::StripPrefixError> {
-        let path = path.strip_prefix(base).map(std::path::Path::to_path_buf)?;
-
-        Ok(Self {
+        raw: Option<Hash>,
+        kind: Kind,
+        size: u64,
+        modified: Option<std::time::SystemTime>,
+        attributes: Option<Attributes>,
+    ) -> Self {
+        Self {
             hash,
+            raw,
             path,
+            kind,
+            size,
+            modified,
+            mode: None,
+            uid: None,
+            gid: None,
             status: Status::Unique,
-        })
+            attributes,
+            link: None,
+            flags: Flags::default(),
+            member: false,
+        }
+    }
+
+    pub(crate) fn linked(&self, path: std::path::PathBuf) -> Self {
+        Self {
+            hash: self.hash.clone(),
+            raw: self.raw.clone(),
+            path,
+            kind: self.kind,
+            size: self.size,
+            modified: self.modified,
+            mode: self.mode,
+            uid: self.uid,
+            gid: self.gid,
+            status: Status::Unique,
+            attributes: self.attributes.clone(),
+            link: self.link,
+            flags: Flags::default(),
+            member: self.member,
+        }
     }
 }
 
@@ -89,44 +224,99 @@ impl std::hash::Hash for Entry {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
-pub struct Hash([u8; 16]);
+/// A content digest, as long as the [`Algorithm`](crate::Algorithm) it was computed with
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, std::hash::Hash)]
+pub struct Hash {
+    bytes: [u8; 32],
+    length: u8,
+}
 
 impl Hash {
-    pub(crate) fn new(array: impl Into<[u8; 16]>) -> Self {
-        Self(array.into())
+    /// The length of the longest supported digest
+    const CAPACITY: usize = 32;
+
+    /// Builds the hash of a digest produced by a hasher, whose length is known to fit
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn new<const N: usize>(digest: [u8; N]) -> Self {
+        const { assert!(N <= Self::CAPACITY, "Digest is too long") };
+        let mut bytes = [0; Self::CAPACITY];
+        bytes[..N].copy_from_slice(&digest);
+        Self {
+            bytes,
+            length: N as u8,
+        }
+    }
+
+    /// Builds the hash of a digest read from elsewhere, `None` if it is longer than any
+    /// supported digest
+    fn from_slice(digest: &[u8]) -> Option<Self> {
+        let mut bytes = [0; Self::CAPACITY];
+        bytes.get_mut(..digest.len())?.copy_from_slice(digest);
+        Some(Self {
+            bytes,
+            length: u8::try_from(digest.len()).ok()?,
+        })
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.length)]
     }
 
     pub(crate) fn decrement(&self) -> Self {
-        let mut hash = self.0;
-        for byte in hash.iter_mut().rev().skip_while(|b| **b == 0).take(1) {
+        let mut hash = self.clone();
+        let length = usize::from(self.length);
+        for byte in hash.bytes[..length]
+            .iter_mut()
+            .rev()
+            .skip_while(|b| **b == 0)
+            .take(1)
+        {
             *byte -= 1;
         }
-        Self(hash)
+        hash
     }
 
     pub(crate) fn first_byte(&self) -> u8 {
-        unsafe { *self.0.get_unchecked(0) }
-    }
-}
-
-impl std::hash::Hash for Hash {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        state.write_u128(u128::from_be_bytes(self.0));
+        self.bytes[0]
     }
 }
 
 impl std::fmt::LowerHex for Hash {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let num = u128::from_be_bytes(self.0);
-        std::fmt::LowerHex::fmt(&num, f)
+        self.as_bytes()
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02x}"))
     }
 }
 
 impl std::fmt::UpperHex for Hash {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let num = u128::from_be_bytes(self.0);
-        std::fmt::UpperHex::fmt(&num, f)
+        self.as_bytes()
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02X}"))
+    }
+}
+
+/// Why a [`Hash`](struct@Hash) could not be parsed
+#[derive(Debug, thiserror::Error)]
+pub enum ParseHashError {
+    #[error("Invalid hexadecimal digest: {0}")]
+    Digits(#[from] std::num::ParseIntError),
+    #[error("Digest of {0} bytes is longer than any supported digest")]
+    Length(usize),
+}
+
+impl std::str::FromStr for Hash {
+    type Err = ParseHashError;
+
+    /// Parses a hexadecimal digest, which must have an even number of digits
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(s.get(i..i + 2).unwrap_or("-"), 16))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_slice(&bytes).ok_or(ParseHashError::Length(bytes.len()))
     }
 }
 
@@ -135,7 +325,7 @@ impl serde::ser::Serialize for Hash {
     where
         S: serde::Serializer,
     {
-        u128::from_be_bytes(self.0).serialize(serializer)
+        serializer.serialize_bytes(self.as_bytes())
     }
 }
 
@@ -144,8 +334,9 @@ impl<'de> serde::de::Deserialize<'de> for Hash {
     where
         D: serde::Deserializer<'de>,
     {
-        let num = u128::deserialize(deserializer)?;
-        Ok(Self(num.to_be_bytes()))
+        let bytes = <Vec<u8>>::deserialize(deserializer)?;
+        Self::from_slice(&bytes)
+            .ok_or_else(|| serde::de::Error::invalid_length(bytes.len(), &"at most 32 bytes"))
     }
 }
 
@@ -153,7 +344,14 @@ impl<'de> serde::de::Deserialize<'de> for Hash {
 pub enum Status {
     Same(usize),
     Moved(usize),
-    Modified(usize),
+    /// The entry changed, along with how similar both contents are in percent, if scored
+    Modified(usize, Option<u8>),
+    /// The `right` entry is the `left` entry with the given number of bytes appended
+    Appended(usize, u64),
+    /// The `right` entry is the `left` entry with the given number of bytes truncated
+    Truncated(usize, u64),
+    /// The same path holds a different [`Kind`] on the other side
+    TypeChanged(Kind),
     Maybe(Vec<usize>),
     Unique,
     Empty,
@@ -164,10 +362,13 @@ impl Status {
         match self {
             Status::Same(_) => 0,
             Status::Moved(_) => 1,
-            Status::Modified(_) => 2,
-            Status::Maybe(_) => 3,
-            Status::Unique => 4,
-            Status::Empty => 5,
+            Status::Modified(..) => 2,
+            Status::Appended(..) => 3,
+            Status::Truncated(..) => 4,
+            Status::TypeChanged(_) => 5,
+            Status::Maybe(_) => 6,
+            Status::Unique => 7,
+            Status::Empty => 8,
         }
     }
 }
@@ -177,7 +378,10 @@ impl std::fmt::Display for Status {
         match self {
             Status::Same(_) => f.write_str("SAME"),
             Status::Moved(_) => f.write_str("MOVED"),
-            Status::Modified(_) => f.write_str("MODIFIED"),
+            Status::Modified(..) => f.write_str("MODIFIED"),
+            Status::Appended(..) => f.write_str("APPENDED"),
+            Status::Truncated(..) => f.write_str("TRUNCATED"),
+            Status::TypeChanged(_) => f.write_str("TYPECHANGED"),
             Status::Maybe(_) => f.write_str("MAYBE"),
             Status::Unique => f.write_str("UNIQUE"),
             Status::Empty => f.write_str("EMPTY"),
@@ -196,3 +400,176 @@ impl std::cmp::PartialOrd for Status {
         Some(self.cmp(other))
     }
 }
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Kind {
+    File,
+    Directory,
+    Symlink,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::File => f.write_str("file"),
+            Kind::Directory => f.write_str("directory"),
+            Kind::Symlink => f.write_str("symlink"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Attributes(Vec<(std::ffi::OsString, Vec<u8>)>);
+
+impl Attributes {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&std::ffi::OsStr, &[u8])> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_os_str(), value.as_slice()))
+    }
+}
+
+impl Attributes {
+    pub(crate) fn new(mut attributes: Vec<(std::ffi::OsString, Vec<u8>)>) -> Self {
+        attributes.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Self(attributes)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Link {
+    group: usize,
+    size: usize,
+}
+
+impl Link {
+    /// Identifies the inode shared by the entries of this group, unique within a [`Directory`]
+    #[must_use]
+    pub fn group(&self) -> usize {
+        self.group
+    }
+
+    /// How many entries in the [`Directory`] share the inode
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Link {
+    pub(crate) fn new(group: usize, size: usize) -> Self {
+        Self { group, size }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Flags(u8);
+
+impl Flags {
+    /// The extended attributes differ from the linked entry
+    pub const ATTRIBUTES: Self = Self(1);
+    /// The entry is hardlinked differently than the linked entry, e.g. hardlinked on one side
+    /// and a separate copy on the other
+    pub const HARDLINKS: Self = Self(1 << 1);
+    /// The path differs from the linked entry only by Unicode normalization, case or the extension
+    /// of a decompressed file
+    pub const NAME: Self = Self(1 << 2);
+    /// The content is only equal to the linked entry after normalization, canonicalization or
+    /// decompression
+    pub const NORMALIZED: Self = Self(1 << 3);
+    /// The entry is within a directory that is a file on the other side, see
+    /// [`TypeChanged`](Status::TypeChanged)
+    pub const RETYPED: Self = Self(1 << 4);
+    /// The mode or the owner differs from the linked entry, see
+    /// [`permissions`](crate::Options::permissions)
+    pub const PERMISSIONS: Self = Self(1 << 5);
+
+    const NAMES: [(Self, &'static str); 6] = [
+        (Self::ATTRIBUTES, "ATTRIBUTES"),
+        (Self::HARDLINKS, "HARDLINKS"),
+        (Self::NAME, "NAME"),
+        (Self::NORMALIZED, "NORMALIZED"),
+        (Self::RETYPED, "RETYPED"),
+        (Self::PERMISSIONS, "PERMISSIONS"),
+    ];
+
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    #[must_use]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Flags {
+    pub(crate) fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl std::fmt::Display for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (flag, name) in Self::NAMES {
+            if self.contains(flag) {
+                write!(f, "+{name}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stores times as signed seconds and nanoseconds since the epoch, since serde only handles times
+/// after it
+mod timestamp {
+    use serde::{Deserialize, Serialize};
+
+    // The signature serde calls with
+    #[allow(clippy::ref_option)]
+    pub fn serialize<S>(
+        time: &Option<std::time::SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        time.map(|time| match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(after) => (after.as_secs().cast_signed(), after.subsec_nanos()),
+            // Nanoseconds always count forward, from the second before
+            Err(before) => {
+                let before = before.duration();
+                match before.subsec_nanos() {
+                    0 => (-before.as_secs().cast_signed(), 0),
+                    nanos => (-before.as_secs().cast_signed() - 1, 1_000_000_000 - nanos),
+                }
+            }
+        })
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<std::time::SystemTime>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let Some((seconds, nanos)) = Option::<(i64, u32)>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+
+        let whole = std::time::Duration::from_secs(seconds.unsigned_abs());
+        if seconds < 0 {
+            std::time::UNIX_EPOCH.checked_sub(whole)
+        } else {
+            std::time::UNIX_EPOCH.checked_add(whole)
+        }
+        .and_then(|second| second.checked_add(std::time::Duration::from_nanos(nanos.into())))
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom("time out of range"))
+    }
+}
@@ -0,0 +1,191 @@
+use super::{entry, source};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not open git repository {0}: {1}")]
+    Open(std::path::PathBuf, git2::Error),
+    #[error("Could not resolve `{0}` to a tree: {1}")]
+    Revision(String, git2::Error),
+    #[error("Could not read tree of `{0}`: {1}")]
+    Tree(String, git2::Error),
+}
+
+/// The tree of a revision of a git repository, read from its object database without checking it
+/// out, under the path of the repository
+pub struct Git {
+    path: std::path::PathBuf,
+    repository: std::sync::Mutex<git2::Repository>,
+    nodes: std::collections::BTreeMap<std::path::PathBuf, Node>,
+}
+
+enum Node {
+    Blob(git2::Oid),
+    Tree,
+    Symlink(std::path::PathBuf),
+}
+
+impl Git {
+    /// Opens the repository at `path`, bare or not, and lists the tree `revision` resolves to,
+    /// e.g. a commit, a branch or a tag
+    ///
+    /// Submodules are not part of the tree and are skipped
+    ///
+    /// # Errors
+    ///
+    /// If `path` is not a repository, or `revision` does not resolve to a tree
+    pub fn open(path: std::path::PathBuf, revision: &str) -> Result<Self, super::Error> {
+        let repository = git2::Repository::open(&path).map_err(|e| Error::Open(path.clone(), e))?;
+        let mut nodes = std::collections::BTreeMap::new();
+        {
+            let tree = repository
+                .revparse_single(revision)
+                .and_then(|object| object.peel_to_tree())
+                .map_err(|e| Error::Revision(String::from(revision), e))?;
+            list(&repository, &tree, &path, &mut nodes)
+                .map_err(|e| Error::Tree(String::from(revision), e))?;
+        }
+
+        Ok(Self {
+            path,
+            repository: std::sync::Mutex::new(repository),
+            nodes,
+        })
+    }
+
+    fn node(&self, path: &std::path::Path) -> std::io::Result<&Node> {
+        if path == self.path {
+            return Ok(&Node::Tree);
+        }
+        self.nodes
+            .get(path)
+            .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+    }
+
+    /// Follows symlinks to the node at the end of the chain, which must be within the tree
+    fn follow(&self, path: &std::path::Path) -> std::io::Result<&Node> {
+        let mut path = path.to_path_buf();
+        // As many hops as the kernel follows before giving up with `ELOOP`
+        for _ in 0..40 {
+            match self.node(&path)? {
+                Node::Symlink(target) => path = source::resolve(&path, target),
+                node => return Ok(node),
+            }
+        }
+        Err(std::io::Error::other("too many levels of symbolic links"))
+    }
+
+    fn metadata(&self, node: &Node) -> std::io::Result<source::Metadata> {
+        let (kind, size) = match node {
+            Node::Blob(oid) => {
+                let repository = self.repository()?;
+                let (size, _) = repository
+                    .odb()
+                    .and_then(|odb| odb.read_header(*oid))
+                    .map_err(std::io::Error::other)?;
+                (entry::Kind::File, size as u64)
+            }
+            Node::Tree => (entry::Kind::Directory, 0),
+            Node::Symlink(target) => (entry::Kind::Symlink, target.as_os_str().len() as u64),
+        };
+        // Git does not track when files were modified
+        Ok(source::Metadata {
+            kind,
+            size,
+            modified: None,
+            changed: None,
+            inode: None,
+            links: 1,
+            mode: None,
+            uid: None,
+            gid: None,
+        })
+    }
+
+    fn repository(&self) -> std::io::Result<std::sync::MutexGuard<'_, git2::Repository>> {
+        self.repository
+            .lock()
+            .map_err(|_| std::io::Error::other("git repository lock poisoned"))
+    }
+}
+
+impl source::FileSystem for Git {
+    type File = std::io::Cursor<Vec<u8>>;
+
+    fn read_dir(&self, path: &std::path::Path) -> std::io::Result<Vec<std::path::PathBuf>> {
+        if !matches!(self.follow(path)?, Node::Tree) {
+            return Err(std::io::ErrorKind::NotADirectory.into());
+        }
+
+        Ok(self
+            .nodes
+            .range(path.to_path_buf()..)
+            .map(|(child, _)| child)
+            .take_while(|child| child.starts_with(path))
+            .filter(|child| child.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn metadata(&self, path: &std::path::Path) -> std::io::Result<source::Metadata> {
+        self.metadata(self.follow(path)?)
+    }
+
+    fn symlink_metadata(&self, path: &std::path::Path) -> std::io::Result<source::Metadata> {
+        self.metadata(self.node(path)?)
+    }
+
+    fn open(&self, path: &std::path::Path) -> std::io::Result<Self::File> {
+        match self.follow(path)? {
+            Node::Blob(oid) => {
+                let repository = self.repository()?;
+                let blob = repository.find_blob(*oid).map_err(std::io::Error::other)?;
+                Ok(std::io::Cursor::new(blob.content().to_vec()))
+            }
+            Node::Tree | Node::Symlink(_) => Err(std::io::ErrorKind::IsADirectory.into()),
+        }
+    }
+
+    fn attributes(&self, _: &std::path::Path) -> std::io::Result<entry::Attributes> {
+        Ok(entry::Attributes::new(Vec::new()))
+    }
+}
+
+/// Adds the content of `tree` under `parent`, recursively
+fn list(
+    repository: &git2::Repository,
+    tree: &git2::Tree,
+    parent: &std::path::Path,
+    nodes: &mut std::collections::BTreeMap<std::path::PathBuf, Node>,
+) -> Result<(), git2::Error> {
+    for member in tree {
+        let path = parent.join(name(member.name_bytes()));
+        match member.kind() {
+            Some(git2::ObjectType::Tree) => {
+                let subtree = repository.find_tree(member.id())?;
+                nodes.insert(path.clone(), Node::Tree);
+                list(repository, &subtree, &path, nodes)?;
+            }
+            Some(git2::ObjectType::Blob) if member.filemode() == 0o120_000 => {
+                let target = repository.find_blob(member.id())?;
+                nodes.insert(path, Node::Symlink(name(target.content())));
+            }
+            Some(git2::ObjectType::Blob) => {
+                nodes.insert(path, Node::Blob(member.id()));
+            }
+            _ => log::warn!("Skipping submodule at {}", path.display()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn name(bytes: &[u8]) -> std::path::PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::path::PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn name(bytes: &[u8]) -> std::path::PathBuf {
+    std::path::PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
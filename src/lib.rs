@@ -1,9 +1,44 @@
+mod agent;
+mod algorithm;
+mod archive;
+mod cache;
+mod canonicalize;
+mod checksum;
 mod crawler;
+mod decompress;
+mod diff;
+mod dupes;
 mod entry;
+mod git;
 mod linker;
+mod matrix;
+mod merge;
+mod mtree;
+mod names;
+mod normalize;
+mod options;
+mod similarity;
+mod source;
 mod thread;
+mod verify;
 
-pub use entry::{Directory, Entry, Hash, Status};
+pub use agent::{index_remote, serve};
+pub use algorithm::Algorithm;
+pub use archive::is_archive;
+pub use canonicalize::{Canonicalization, Canonicalizer, Command, Json};
+pub use checksum::{read_checksums, write_checksums};
+pub use decompress::{Compression, Decompression};
+pub use diff::{Diff, diff};
+pub use dupes::{Duplicates, dupes};
+pub use entry::{Attributes, Directory, Entry, Flags, Hash, Kind, Link, ParseHashError, Status};
+pub use matrix::{Agreement, Cell, Group, Matrix, Row, compare_all, link_all};
+pub use merge::{Change, Conflict, Merge, Outcome, compare_three, link_three};
+pub use mtree::{read_mtree, write_mtree};
+pub use normalize::{Normalization, Rules};
+pub use options::{Options, Settings};
+pub use similarity::similarity;
+pub use source::{FileSystem, Local, Memory, Metadata};
+pub use verify::{Verdict, verify};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -11,11 +46,32 @@ pub enum Error {
     Thread(#[from] thread::Error),
     #[error(transparent)]
     Crawler(#[from] crawler::Error),
+    #[error(transparent)]
+    Cache(#[from] cache::Error),
+    #[error(transparent)]
+    Agent(#[from] agent::Error),
+    #[error(transparent)]
+    Archive(#[from] archive::Error),
+    #[error(transparent)]
+    Git(#[from] git::Error),
+    #[error(transparent)]
+    Checksum(#[from] checksum::Error),
+    #[error(transparent)]
+    Mtree(#[from] mtree::Error),
+    #[error("Cannot link directories hashed with {0} and {1}")]
+    Algorithms(Algorithm, Algorithm),
+    #[error(transparent)]
+    Diff(#[from] diff::Error),
+    #[error(transparent)]
+    Similarity(#[from] similarity::Error),
 }
 
 /// Compares two directories [`left`](std::path::PathBuf) and [`right`](std::path::PathBuf)
 /// returning the [`Directory`]
 ///
+/// What gets collected for each [`Entry`] and which extra checks are run while linking is
+/// controlled by the given [`Options`]. Either side may be an archive, see [`index`]
+///
 /// # Errors
 ///
 /// This is a fallible process and will fail-fast.
@@ -25,18 +81,225 @@ pub enum Error {
 pub fn compare(
     left: std::path::PathBuf,
     right: std::path::PathBuf,
+    options: &Options,
 ) -> Result<(entry::Directory, entry::Directory), Error> {
     let pool = thread::pool()?;
-    let mut left_entries = crawler::crawl(&left, &pool)?;
-    let mut right_entries = crawler::crawl(&right, &pool)?;
+    let cache = cache::Cache::open(options);
+    let left = index_with(left, options, cache.as_ref(), &pool)?;
+    let right = index_with(right, options, cache.as_ref(), &pool)?;
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+    link_with(left, right, options, &pool)
+}
+
+/// Indexes a single directory at [`path`](std::path::PathBuf) without comparing it, so it can be
+/// saved as a manifest and [`link`]ed later, possibly on another host
+///
+/// The path may also be a `.tar`, `.tar.gz` or `.tar.zst` archive, see [`is_archive`], whose
+/// members are indexed in place of the content of a directory
+///
+/// # Errors
+///
+/// See [`compare`]
+pub fn index(path: std::path::PathBuf, options: &Options) -> Result<entry::Directory, Error> {
+    let pool = thread::pool()?;
+    let cache = cache::Cache::open(options);
+    let directory = index_with(path, options, cache.as_ref(), &pool)?;
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+    Ok(directory)
+}
+
+/// Indexes the tree at [`path`](std::path::PathBuf) of any [`FileSystem`], such as a [`Memory`]
+/// one, the same way [`index`] does for the local disk
+///
+/// No hash cache is used, and only trees of a [`local`](FileSystem::is_local) file system can have
+/// the checks that read file contents again run on them when linked
+///
+/// # Errors
+///
+/// See [`compare`]
+pub fn index_in<F: FileSystem>(
+    fs: &std::sync::Arc<F>,
+    path: std::path::PathBuf,
+    options: &Options,
+) -> Result<entry::Directory, Error> {
+    let pool = thread::pool()?;
+    crawl(fs, path, options, None, &pool)
+}
+
+/// Indexes the tree that `revision` of the git repository at [`path`](std::path::PathBuf)
+/// resolves to, reading the blobs from the object database instead of a checkout
+///
+/// Git does not record when files were modified, so the entries have no modification time
+///
+/// # Errors
+///
+/// If `path` is not a repository or `revision` does not name a tree, otherwise see [`compare`]
+pub fn index_git(
+    path: std::path::PathBuf,
+    revision: &str,
+    options: &Options,
+) -> Result<entry::Directory, Error> {
+    let pool = thread::pool()?;
+    let git = std::sync::Arc::new(git::Git::open(path.clone(), revision)?);
+    crawl(&git, path, options, None, &pool)
+}
+
+/// Links two indexed directories the same way [`compare`] does, whether they were just indexed
+/// or loaded from manifests
+///
+/// Checks that need the content of the files, i.e. [`prefixes`](Options::prefixes) and
+/// [`similarity`](Options::similarity), are skipped unless both directories were indexed by this
+/// process
+///
+/// # Errors
+///
+/// See [`compare`]
+pub fn link(
+    left: entry::Directory,
+    right: entry::Directory,
+    options: &Options,
+) -> Result<(entry::Directory, entry::Directory), Error> {
+    let pool = thread::pool()?;
+    link_with(left, right, options, &pool)
+}
+
+fn index_with(
+    path: std::path::PathBuf,
+    options: &Options,
+    cache: Option<&std::sync::Arc<cache::Cache>>,
+    pool: &rayon::ThreadPool,
+) -> Result<entry::Directory, Error> {
+    if archive::is_archive(&path) {
+        return archive::read(&path, options);
+    }
+
+    crawl(&std::sync::Arc::new(Local), path, options, cache, pool)
+}
+
+fn crawl<F: FileSystem>(
+    fs: &std::sync::Arc<F>,
+    path: std::path::PathBuf,
+    options: &Options,
+    cache: Option<&std::sync::Arc<cache::Cache>>,
+    pool: &rayon::ThreadPool,
+) -> Result<entry::Directory, Error> {
+    let (entries, directories) = crawler::crawl(fs, &path, options, cache, None, pool)?;
+    let mut directory = entry::Directory::new(path, entries, directories, options.into());
+    // Nothing off the local disk can be read again, members of archives are skipped one by one
+    directory.live = fs.is_local();
+    Ok(directory)
+}
+
+fn link_with(
+    mut left: entry::Directory,
+    mut right: entry::Directory,
+    options: &Options,
+    pool: &rayon::ThreadPool,
+) -> Result<(entry::Directory, entry::Directory), Error> {
+    if left.settings().algorithm != right.settings().algorithm {
+        return Err(Error::Algorithms(
+            left.settings().algorithm,
+            right.settings().algorithm,
+        ));
+    }
+
+    if left.settings() != right.settings() {
+        log::warn!(
+            "{} and {} were indexed with different options, their hashes may not be comparable",
+            left.path().display(),
+            right.path().display(),
+        );
+    }
+
+    // Manifests may have been linked before they were saved
+    for entry in left.entries.iter_mut().chain(right.entries.iter_mut()) {
+        entry.status = entry::Status::Unique;
+        entry.flags = entry::Flags::default();
+    }
+
+    let algorithm = left.settings().algorithm;
+    let empty_hash = algorithm.digest(&[]);
+    let live = left.live && right.live;
+    let typed = !left.untyped && !right.untyped;
+    let (left_path, right_path) = (left.path().to_path_buf(), right.path().to_path_buf());
+    let (left_directories, right_directories) = (&left.directories, &right.directories);
+    let left_entries = &mut left.entries;
+    let right_entries = &mut right.entries;
+
+    let names = names::Names::new(left_entries, right_entries, options, pool);
+
+    linker::first_pass(
+        left_entries,
+        right_entries,
+        &empty_hash,
+        names.as_ref(),
+        pool,
+    );
+    linker::second_pass(
+        left_entries,
+        right_entries,
+        &empty_hash,
+        names.as_ref(),
+        pool,
+    );
+    if typed {
+        linker::types_pass(
+            left_entries,
+            right_entries,
+            left_directories,
+            right_directories,
+        );
+    } else {
+        log::info!("Skipping types pass, a side is a checksum listing");
+    }
+
+    if (options.prefixes || options.similarity) && !live {
+        log::warn!("Skipping the checks that read file contents, a side is not a live directory");
+    }
+
+    if options.prefixes && live {
+        linker::prefix_pass(
+            left_entries,
+            right_entries,
+            &left_path,
+            &right_path,
+            algorithm,
+            &options.decompressions,
+            pool,
+        )?;
+    }
+
+    if options.similarity && live {
+        linker::similarity_pass(
+            left_entries,
+            right_entries,
+            &left_path,
+            &right_path,
+            &options.decompressions,
+            pool,
+        )?;
+    }
+
+    linker::links_pass(left_entries, right_entries);
 
-    let empty_hash = entry::Hash::new(md5::Digest::finalize(<md5::Md5 as md5::Digest>::new()));
+    if options.attributes {
+        linker::attributes_pass(left_entries, right_entries, pool);
+    }
 
-    linker::first_pass(&mut left_entries, &mut right_entries, &empty_hash, &pool);
-    linker::second_pass(&mut left_entries, &mut right_entries, &empty_hash, &pool);
+    if options.permissions {
+        linker::permissions_pass(left_entries, right_entries, pool);
+    }
 
-    let left = entry::Directory::new(left, left_entries);
-    let right = entry::Directory::new(right, right_entries);
+    if !options.normalizations.is_empty()
+        || !options.canonicalizations.is_empty()
+        || !options.decompressions.is_empty()
+    {
+        linker::normalized_pass(left_entries, right_entries, pool);
+    }
 
     Ok((left, right))
 }
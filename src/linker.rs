@@ -1,9 +1,10 @@
-use super::entry;
+use super::{algorithm, crawler, decompress, entry, names, similarity};
 
 pub fn first_pass(
     left: &mut Vec<entry::Entry>,
     right: &mut Vec<entry::Entry>,
     empty_hash: &entry::Hash,
+    names: Option<&names::Names>,
     pool: &rayon::ThreadPool,
 ) {
     use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
@@ -22,6 +23,24 @@ pub fn first_pass(
                         entry.status = entry::Status::Same(i);
                         unsafe { (*ptr.add(i)).status = entry::Status::Same(left_idx) };
                     }
+                    Err(i)
+                        if let Some(i) = names.and_then(|names| {
+                            matching_name(
+                                &entry.hash,
+                                i,
+                                right,
+                                &names.left[left_idx],
+                                &names.right,
+                            )
+                        }) =>
+                    {
+                        entry.status = entry::Status::Same(i);
+                        entry.flags.insert(entry::Flags::NAME);
+                        unsafe {
+                            (*ptr.add(i)).status = entry::Status::Same(left_idx);
+                            (*ptr.add(i)).flags.insert(entry::Flags::NAME);
+                        }
+                    }
                     Err(_) if entry.hash() == empty_hash => {
                         entry.status = entry::Status::Empty;
                     }
@@ -29,10 +48,22 @@ pub fn first_pass(
                         let indices = matching_hashes(&entry.hash, i, right);
                         match indices.len() {
                             0 => {
-                                if let Some(i) = right.iter().position(|e| e.path == entry.path) {
-                                    entry.status = entry::Status::Modified(i);
+                                if let Some(i) =
+                                    right.iter().position(|e| e.path == entry.path).or_else(|| {
+                                        names.and_then(|names| {
+                                            let name = &names.left[left_idx];
+                                            names.right.iter().position(|n| n == name)
+                                        })
+                                    })
+                                {
+                                    entry.status = entry::Status::Modified(i, None);
                                     unsafe {
-                                        (*ptr.add(i)).status = entry::Status::Modified(left_idx);
+                                        (*ptr.add(i)).status =
+                                            entry::Status::Modified(left_idx, None);
+                                    }
+                                    if right[i].path != entry.path {
+                                        entry.flags.insert(entry::Flags::NAME);
+                                        unsafe { (*ptr.add(i)).flags.insert(entry::Flags::NAME) };
                                     }
                                 } else {
                                     entry.status = entry::Status::Unique;
@@ -52,9 +83,10 @@ pub fn second_pass(
     left: &mut Vec<entry::Entry>,
     right: &mut Vec<entry::Entry>,
     empty_hash: &entry::Hash,
+    names: Option<&names::Names>,
     pool: &rayon::ThreadPool,
 ) {
-    use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+    use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
 
     log::info!("Starting second pass");
     let start = std::time::Instant::now();
@@ -63,8 +95,9 @@ pub fn second_pass(
     pool.install(|| {
         right
             .par_iter_mut()
-            .filter(|e| matches!(e.status, entry::Status::Unique))
-            .for_each(|entry| {
+            .enumerate()
+            .filter(|(_, e)| matches!(e.status, entry::Status::Unique))
+            .for_each(|(right_idx, entry)| {
                 let ptr = ptr as *mut entry::Entry;
                 match left.binary_search(entry) {
                     Ok(i) => {
@@ -74,6 +107,18 @@ pub fn second_pass(
                             entry.path.display()
                         );
                     }
+                    Err(i)
+                        if let Some(i) = names.and_then(|names| {
+                            matching_name(&entry.hash, i, left, &names.right[right_idx], &names.left)
+                        }) =>
+                    {
+                        entry.status = entry::Status::Same(i);
+                        entry.flags.insert(entry::Flags::NAME);
+                        log::warn!(
+                            "Marking unexpected `SAME` on second pass for {}",
+                            entry.path.display()
+                        );
+                    }
                     Err(_) if entry.hash() == empty_hash => {
                         entry.status = entry::Status::Empty;
                     }
@@ -111,6 +156,344 @@ pub fn second_pass(
     log::info!("Finished second pass in {:?}", start.elapsed());
 }
 
+pub fn types_pass(
+    left: &mut [entry::Entry],
+    right: &mut [entry::Entry],
+    left_directories: &[std::path::PathBuf],
+    right_directories: &[std::path::PathBuf],
+) {
+    log::info!("Starting types pass");
+    let start = std::time::Instant::now();
+
+    let changed = left
+        .iter()
+        .enumerate()
+        .filter_map(|(left_idx, entry)| {
+            // Both pairs share the same path, and none is `Appended` or `Truncated` yet since
+            // the prefix pass runs after this one
+            let (entry::Status::Same(right_idx) | entry::Status::Modified(right_idx, _)) =
+                entry.status
+            else {
+                return None;
+            };
+            (entry.kind != right[right_idx].kind).then_some((left_idx, right_idx))
+        })
+        .collect::<Vec<_>>();
+
+    for (left_idx, right_idx) in changed {
+        let left_kind = left[left_idx].kind;
+        left[left_idx].status = entry::Status::TypeChanged(right[right_idx].kind);
+        right[right_idx].status = entry::Status::TypeChanged(left_kind);
+    }
+
+    retype_directories(left, right_directories, right);
+    retype_directories(right, left_directories, left);
+
+    log::info!("Finished types pass in {:?}", start.elapsed());
+}
+
+/// Marks the entries that are directories among the `directories` of the `other` side as type
+/// changes, and flags the entries of `other` within them, which would otherwise show up as unique
+/// with nothing tying them to the change
+fn retype_directories(
+    entries: &mut [entry::Entry],
+    directories: &[std::path::PathBuf],
+    other: &mut [entry::Entry],
+) {
+    if directories.is_empty() {
+        return;
+    }
+
+    let mut retyped = std::collections::HashSet::new();
+    for entry in entries
+        .iter_mut()
+        .filter(|e| matches!(e.status, entry::Status::Unique | entry::Status::Empty))
+    {
+        if directories.binary_search(&entry.path).is_ok() {
+            entry.status = entry::Status::TypeChanged(entry::Kind::Directory);
+            retyped.insert(entry.path.clone());
+        }
+    }
+
+    if retyped.is_empty() {
+        return;
+    }
+    for entry in other
+        .iter_mut()
+        .filter(|e| e.path.ancestors().skip(1).any(|a| retyped.contains(a)))
+    {
+        entry.flags.insert(entry::Flags::RETYPED);
+    }
+}
+
+pub fn prefix_pass(
+    left: &mut [entry::Entry],
+    right: &mut [entry::Entry],
+    left_path: &std::path::Path,
+    right_path: &std::path::Path,
+    algorithm: algorithm::Algorithm,
+    decompressions: &[decompress::Decompression],
+    pool: &rayon::ThreadPool,
+) -> Result<(), crawler::Error> {
+    use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+    log::info!("Starting prefix pass");
+    let start = std::time::Instant::now();
+
+    let prefixes = pool.install(|| {
+        left.par_iter()
+            .enumerate()
+            .filter_map(|(left_idx, entry)| {
+                let entry::Status::Modified(right_idx, _) = entry.status else {
+                    return None;
+                };
+
+                let other = &right[right_idx];
+                if unreadable(entry, decompressions) || unreadable(other, decompressions) {
+                    return None;
+                }
+                let (shorter, longer, path) = match entry.size.cmp(&other.size) {
+                    std::cmp::Ordering::Less => (entry, other, right_path),
+                    std::cmp::Ordering::Greater => (other, entry, left_path),
+                    std::cmp::Ordering::Equal => return None,
+                };
+
+                crawler::hash_prefix(&path.join(&longer.path), shorter.size, algorithm)
+                    .map(|hash| {
+                        (hash == *shorter.raw_hash()).then_some((
+                            left_idx,
+                            right_idx,
+                            entry.size < other.size,
+                            longer.size - shorter.size,
+                        ))
+                    })
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+
+    for (left_idx, right_idx, appended, delta) in prefixes {
+        if appended {
+            left[left_idx].status = entry::Status::Appended(right_idx, delta);
+            right[right_idx].status = entry::Status::Appended(left_idx, delta);
+        } else {
+            left[left_idx].status = entry::Status::Truncated(right_idx, delta);
+            right[right_idx].status = entry::Status::Truncated(left_idx, delta);
+        }
+    }
+
+    log::info!("Finished prefix pass in {:?}", start.elapsed());
+    Ok(())
+}
+
+pub fn similarity_pass(
+    left: &mut [entry::Entry],
+    right: &mut [entry::Entry],
+    left_path: &std::path::Path,
+    right_path: &std::path::Path,
+    decompressions: &[decompress::Decompression],
+    pool: &rayon::ThreadPool,
+) -> Result<(), super::Error> {
+    use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+    log::info!("Starting similarity pass");
+    let start = std::time::Instant::now();
+
+    let scores = pool.install(|| {
+        left.par_iter()
+            .enumerate()
+            .filter_map(|(left_idx, entry)| {
+                let entry::Status::Modified(right_idx, _) = entry.status else {
+                    return None;
+                };
+                if unreadable(entry, decompressions)
+                    || unreadable(&right[right_idx], decompressions)
+                {
+                    return None;
+                }
+
+                Some(
+                    similarity::similarity(
+                        &left_path.join(&entry.path),
+                        &right_path.join(&right[right_idx].path),
+                    )
+                    .map(|score| (left_idx, right_idx, score)),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+
+    for (left_idx, right_idx, score) in scores {
+        left[left_idx].status = entry::Status::Modified(right_idx, Some(score));
+        right[right_idx].status = entry::Status::Modified(left_idx, Some(score));
+    }
+
+    log::info!("Finished similarity pass in {:?}", start.elapsed());
+    Ok(())
+}
+
+/// Whether the checks reading the files again would not see what was hashed, since the entry is
+/// a member of an archive or was hashed by its decompressed content
+fn unreadable(entry: &entry::Entry, decompressions: &[decompress::Decompression]) -> bool {
+    entry.member || decompress::find(&entry.path, decompressions).is_some()
+}
+
+pub fn attributes_pass(
+    left: &mut [entry::Entry],
+    right: &mut [entry::Entry],
+    pool: &rayon::ThreadPool,
+) {
+    log::info!("Starting attributes pass");
+    let start = std::time::Instant::now();
+
+    let differs = |entry: &entry::Entry, other: &entry::Entry| entry.attributes != other.attributes;
+    pool.install(|| {
+        flag_pairs(left, right, entry::Flags::ATTRIBUTES, differs);
+        flag_pairs(right, left, entry::Flags::ATTRIBUTES, differs);
+    });
+
+    log::info!("Finished attributes pass in {:?}", start.elapsed());
+}
+
+pub fn permissions_pass(
+    left: &mut [entry::Entry],
+    right: &mut [entry::Entry],
+    pool: &rayon::ThreadPool,
+) {
+    log::info!("Starting permissions pass");
+    let start = std::time::Instant::now();
+
+    // Sides that do not know a field, such as git trees or archives, agree with anything
+    let known = |a: Option<u32>, b: Option<u32>| a.zip(b).is_some_and(|(a, b)| a != b);
+    let differs = |entry: &entry::Entry, other: &entry::Entry| {
+        known(entry.mode, other.mode) || known(entry.uid, other.uid) || known(entry.gid, other.gid)
+    };
+    pool.install(|| {
+        flag_pairs(left, right, entry::Flags::PERMISSIONS, differs);
+        flag_pairs(right, left, entry::Flags::PERMISSIONS, differs);
+    });
+
+    log::info!("Finished permissions pass in {:?}", start.elapsed());
+}
+
+pub fn normalized_pass(
+    left: &mut [entry::Entry],
+    right: &mut [entry::Entry],
+    pool: &rayon::ThreadPool,
+) {
+    log::info!("Starting normalized pass");
+    let start = std::time::Instant::now();
+
+    let differs = |entry: &entry::Entry, other: &entry::Entry| {
+        entry.hash == other.hash && entry.raw_hash() != other.raw_hash()
+    };
+    pool.install(|| {
+        flag_pairs(left, right, entry::Flags::NORMALIZED, differs);
+        flag_pairs(right, left, entry::Flags::NORMALIZED, differs);
+    });
+
+    log::info!("Finished normalized pass in {:?}", start.elapsed());
+}
+
+fn flag_pairs(
+    entries: &mut [entry::Entry],
+    others: &[entry::Entry],
+    flag: entry::Flags,
+    differs: impl Fn(&entry::Entry, &entry::Entry) -> bool + Sync,
+) {
+    use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+    entries.par_iter_mut().for_each(|entry| {
+        let Some(i) = correspondent(&entry.status) else {
+            return;
+        };
+
+        if differs(entry, &others[i]) {
+            entry.flags.insert(flag);
+        }
+    });
+}
+
+pub fn links_pass(left: &mut [entry::Entry], right: &mut [entry::Entry]) {
+    if left.iter().chain(right.iter()).all(|e| e.link.is_none()) {
+        return;
+    }
+
+    log::info!("Starting links pass");
+    let start = std::time::Instant::now();
+
+    flag_links(left, right);
+    flag_links(right, left);
+
+    log::info!("Finished links pass in {:?}", start.elapsed());
+}
+
+fn flag_links(entries: &mut [entry::Entry], others: &[entry::Entry]) {
+    // Maps each link group to the group of its correspondents on the other side, or to `None`
+    // if the correspondents are not all hardlinked together
+    let mut groups = std::collections::HashMap::new();
+    for entry in entries.iter() {
+        let (Some(link), Some(i)) = (entry.link, correspondent(&entry.status)) else {
+            continue;
+        };
+
+        let other = others[i].link.map(|l| l.group());
+        groups
+            .entry(link.group())
+            .and_modify(|group: &mut Option<usize>| {
+                if *group != other {
+                    *group = None;
+                }
+            })
+            .or_insert(other);
+    }
+
+    for entry in entries.iter_mut() {
+        let Some(i) = correspondent(&entry.status) else {
+            continue;
+        };
+
+        let differs = match (entry.link, others[i].link) {
+            (None, None) => false,
+            (Some(link), Some(other)) => {
+                link.size() != other.size()
+                    || groups.get(&link.group()) != Some(&Some(other.group()))
+            }
+            _ => true,
+        };
+
+        if differs {
+            entry.flags.insert(entry::Flags::HARDLINKS);
+        }
+    }
+}
+
+fn correspondent(status: &entry::Status) -> Option<usize> {
+    match status {
+        entry::Status::Same(i)
+        | entry::Status::Moved(i)
+        | entry::Status::Modified(i, _)
+        | entry::Status::Appended(i, _)
+        | entry::Status::Truncated(i, _) => Some(*i),
+        entry::Status::TypeChanged(_)
+        | entry::Status::Maybe(_)
+        | entry::Status::Unique
+        | entry::Status::Empty => None,
+    }
+}
+
+fn matching_name(
+    hash: &entry::Hash,
+    pivot: usize,
+    children: &[entry::Entry],
+    name: &std::path::Path,
+    names: &[std::path::PathBuf],
+) -> Option<usize> {
+    matching_hashes(hash, pivot, children)
+        .into_iter()
+        .find(|i| names[*i] == name)
+}
+
 fn matching_hashes(hash: &entry::Hash, pivot: usize, children: &[entry::Entry]) -> Vec<usize> {
     let i = match children[..pivot].binary_search_by(|e| e.hash.cmp(&hash.decrement())) {
         Ok(i) | Err(i) => i,
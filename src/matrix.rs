@@ -0,0 +1,255 @@
+use super::{entry, names, options, thread};
+
+/// Where each path stands across any number of indexed directories, one [`Row`] per path
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Matrix {
+    sides: Vec<std::path::PathBuf>,
+    rows: Vec<Row>,
+}
+
+impl Matrix {
+    /// The paths of the compared directories, in the order of the [`cells`](Row::cells)
+    #[must_use]
+    pub fn sides(&self) -> &[std::path::PathBuf] {
+        &self.sides
+    }
+
+    /// The rows sorted by path
+    #[must_use]
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+}
+
+/// A path and the content it holds on every side
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Row {
+    path: std::path::PathBuf,
+    groups: Vec<Group>,
+    cells: Vec<Cell>,
+}
+
+impl Row {
+    /// The path relative to the compared directories, as found on the first side holding it
+    #[must_use]
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// The distinct contents found at the path, held by the most sides first
+    #[must_use]
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// What each side holds at the path, in the order of the [`sides`](Matrix::sides)
+    #[must_use]
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    #[must_use]
+    pub fn agreement(&self) -> Agreement {
+        if self.groups.len() > 1 {
+            Agreement::Diverged
+        } else if self.cells.iter().all(|cell| matches!(cell, Cell::Group(_))) {
+            Agreement::Same
+        } else {
+            Agreement::Missing
+        }
+    }
+}
+
+/// A content held at the path of a [`Row`] by some of the sides
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Group {
+    pub hash: entry::Hash,
+    pub size: u64,
+    pub kind: entry::Kind,
+}
+
+/// What a side holds at the path of a [`Row`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Cell {
+    /// The content of the [`group`](Row::groups) at the given index
+    Group(usize),
+    /// Nothing, but the content of one of the groups is found at another path of the side
+    Elsewhere,
+    Absent,
+}
+
+/// How the sides agree on the content of a path
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Agreement {
+    /// Every side holds the same content
+    Same,
+    /// The sides holding the path agree, but some do not hold it
+    Missing,
+    /// The sides holding the path hold different contents
+    Diverged,
+}
+
+impl std::fmt::Display for Agreement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Same => f.pad("SAME"),
+            Self::Missing => f.pad("MISSING"),
+            Self::Diverged => f.pad("DIVERGED"),
+        }
+    }
+}
+
+/// Indexes every directory at [`paths`](std::path::PathBuf) and compares them all at once, see
+/// [`link_all`]
+///
+/// # Errors
+///
+/// See [`compare`](crate::compare)
+pub fn compare_all(
+    paths: Vec<std::path::PathBuf>,
+    options: &options::Options,
+) -> Result<Matrix, super::Error> {
+    let pool = thread::pool()?;
+    let cache = super::cache::Cache::open(options);
+    let directories = paths
+        .into_iter()
+        .map(|path| super::index_with(path, options, cache.as_ref(), &pool))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+    link_all(directories, options)
+}
+
+/// Lays out which of the indexed `directories` hold each path and which of them agree on its
+/// content, whether they were just indexed or loaded from manifests
+///
+/// Paths are matched the way [`link`](crate::link) matches them by name, so
+/// [`unicode`](options::Options::unicode), [`ignore_case`](options::Options::ignore_case) and
+/// [`decompressions`](options::Options::decompressions) apply. Only contents are compared, none
+/// of the checks that flag linked pairs are run
+///
+/// # Errors
+///
+/// If the directories were not all hashed with the same [`Algorithm`](crate::Algorithm)
+pub fn link_all(
+    directories: Vec<entry::Directory>,
+    options: &options::Options,
+) -> Result<Matrix, super::Error> {
+    if let Some(first) = directories.first() {
+        let algorithm = first.settings().algorithm;
+        if let Some(other) = directories
+            .iter()
+            .find(|directory| directory.settings().algorithm != algorithm)
+        {
+            return Err(super::Error::Algorithms(
+                algorithm,
+                other.settings().algorithm,
+            ));
+        }
+        if directories
+            .iter()
+            .any(|directory| directory.settings() != first.settings())
+        {
+            log::warn!(
+                "The directories were indexed with different options, their hashes may not be comparable"
+            );
+        }
+    }
+
+    log::info!("Laying out {} directories", directories.len());
+    let start = std::time::Instant::now();
+
+    let fold = names::folds(options);
+    let count = directories.len();
+    let mut keys = std::collections::HashMap::new();
+    let mut found = Vec::<(std::path::PathBuf, Vec<Option<Group>>)>::new();
+    let mut contents = Vec::with_capacity(count);
+    let mut sides = Vec::with_capacity(count);
+    for (side, directory) in directories.into_iter().enumerate() {
+        let (path, entries) = directory.decompose();
+        sides.push(path);
+        contents.push(
+            entries
+                .iter()
+                .map(|entry| entry.hash.clone())
+                .collect::<std::collections::HashSet<_>>(),
+        );
+
+        for entry in entries {
+            let key = if fold {
+                names::fold(&entry.path, options)
+            } else {
+                entry.path.clone()
+            };
+            let row = *keys.entry(key).or_insert_with(|| {
+                found.push((entry.path.clone(), vec![None; count]));
+                found.len() - 1
+            });
+            found[row].1[side] = Some(Group {
+                hash: entry.hash,
+                size: entry.size,
+                kind: entry.kind,
+            });
+        }
+    }
+
+    let mut rows = found
+        .into_iter()
+        .map(|(path, held)| row(path, &held, &contents))
+        .collect::<Vec<_>>();
+    rows.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+    log::info!(
+        "Finished laying out {} paths in {:?}",
+        rows.len(),
+        start.elapsed()
+    );
+    Ok(Matrix { sides, rows })
+}
+
+/// Groups the sides by the content they hold at `path`, the largest group first and the one of
+/// the first side among groups as large
+fn row(
+    path: std::path::PathBuf,
+    held: &[Option<Group>],
+    contents: &[std::collections::HashSet<entry::Hash>],
+) -> Row {
+    let mut groups = Vec::<(Group, usize, usize)>::new();
+    for (side, group) in held.iter().enumerate() {
+        let Some(group) = group else {
+            continue;
+        };
+        match groups.iter_mut().find(|(g, ..)| g.hash == group.hash) {
+            Some((_, count, _)) => *count += 1,
+            None => groups.push((group.clone(), 1, side)),
+        }
+    }
+    groups.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+
+    let cells = held
+        .iter()
+        .enumerate()
+        .map(|(side, group)| match group {
+            Some(group) => Cell::Group(
+                groups
+                    .iter()
+                    .position(|(g, ..)| g.hash == group.hash)
+                    .unwrap_or_default(),
+            ),
+            None if groups
+                .iter()
+                .any(|(g, ..)| contents[side].contains(&g.hash)) =>
+            {
+                Cell::Elsewhere
+            }
+            None => Cell::Absent,
+        })
+        .collect();
+
+    Row {
+        path,
+        groups: groups.into_iter().map(|(group, ..)| group).collect(),
+        cells,
+    }
+}
@@ -0,0 +1,429 @@
+use super::{entry, names, options, thread};
+
+/// How two directories `left` and `right` changed from a common `base`, one [`Change`] per
+/// entry of any of them
+#[derive(Debug)]
+pub struct Merge {
+    base: entry::Directory,
+    left: entry::Directory,
+    right: entry::Directory,
+    changes: Vec<Change>,
+}
+
+impl Merge {
+    #[must_use]
+    pub fn base(&self) -> &entry::Directory {
+        &self.base
+    }
+
+    #[must_use]
+    pub fn left(&self) -> &entry::Directory {
+        &self.left
+    }
+
+    #[must_use]
+    pub fn right(&self) -> &entry::Directory {
+        &self.right
+    }
+
+    /// The changes sorted by the path they are known by, i.e. their path in `base` if they have
+    /// one
+    #[must_use]
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// The entries a change is made of in `base`, `left` and `right`
+    #[must_use]
+    pub fn entries(
+        &self,
+        change: &Change,
+    ) -> (
+        Option<&entry::Entry>,
+        Option<&entry::Entry>,
+        Option<&entry::Entry>,
+    ) {
+        (
+            change.base.map(|i| &self.base.entries[i]),
+            change.left.map(|i| &self.left.entries[i]),
+            change.right.map(|i| &self.right.entries[i]),
+        )
+    }
+
+    #[must_use]
+    pub fn decompose(self) -> (entry::Directory, entry::Directory, entry::Directory) {
+        (self.base, self.left, self.right)
+    }
+}
+
+/// An entry of `base` and what became of it on each side, or an entry added by either side
+///
+/// A side that does not hold the entry deleted it, or never had it if there is no `base` entry
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Change {
+    base: Option<usize>,
+    left: Option<usize>,
+    right: Option<usize>,
+    outcome: Outcome,
+}
+
+impl Change {
+    /// The index of the entry in the entries of [`base`](Merge::base)
+    #[must_use]
+    pub fn base(&self) -> Option<usize> {
+        self.base
+    }
+
+    /// The index of the entry in the entries of [`left`](Merge::left)
+    #[must_use]
+    pub fn left(&self) -> Option<usize> {
+        self.left
+    }
+
+    /// The index of the entry in the entries of [`right`](Merge::right)
+    #[must_use]
+    pub fn right(&self) -> Option<usize> {
+        self.right
+    }
+
+    #[must_use]
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+}
+
+/// Which sides changed an entry, and whether their changes can be combined
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    /// Neither side changed the entry
+    Unchanged,
+    /// Only `left` changed the entry, so its version is taken
+    Left,
+    /// Only `right` changed the entry, so its version is taken
+    Right,
+    /// Both sides changed the entry the same way
+    Both,
+    /// One side only moved the entry and the other only changed its content, so the content of
+    /// the latter is taken to the path of the former
+    Combined,
+    Conflict(Conflict),
+}
+
+/// Why the changes of both sides cannot be combined
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Conflict {
+    /// Both sides changed the content or path of the entry, differently
+    Modified,
+    /// One side deleted the entry, the other changed it
+    Deleted,
+    /// Both sides added different entries at the same path
+    Added,
+    /// Changes of both sides that are fine on their own end up at the same path
+    Path,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unchanged => f.pad("UNCHANGED"),
+            Self::Left => f.pad("LEFT"),
+            Self::Right => f.pad("RIGHT"),
+            Self::Both => f.pad("BOTH"),
+            Self::Combined => f.pad("COMBINED"),
+            Self::Conflict(_) => f.pad("CONFLICT"),
+        }
+    }
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Modified => f.pad("both modified"),
+            Self::Deleted => f.pad("modified and deleted"),
+            Self::Added => f.pad("both added"),
+            Self::Path => f.pad("same path"),
+        }
+    }
+}
+
+/// Indexes the directories at [`base`](std::path::PathBuf), [`left`](std::path::PathBuf) and
+/// [`right`](std::path::PathBuf) and merges them, see [`link_three`]
+///
+/// # Errors
+///
+/// See [`compare`](crate::compare)
+pub fn compare_three(
+    base: std::path::PathBuf,
+    left: std::path::PathBuf,
+    right: std::path::PathBuf,
+    options: &options::Options,
+) -> Result<Merge, super::Error> {
+    let pool = thread::pool()?;
+    let cache = super::cache::Cache::open(options);
+    let base = super::index_with(base, options, cache.as_ref(), &pool)?;
+    let left = super::index_with(left, options, cache.as_ref(), &pool)?;
+    let right = super::index_with(right, options, cache.as_ref(), &pool)?;
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+    merge_with(base, left, right, options, &pool)
+}
+
+/// Tells which changes of `left` and `right` from their common `base` can be combined, whether
+/// they were just indexed or loaded from manifests
+///
+/// Each side is [`link`](crate::link)ed with `base` first, so the entries of `base` it moved,
+/// modified or deleted are found the same way. The entries a side holds that are not linked to
+/// any entry of `base` were added by it, including copies of an entry of `base` held at several
+/// paths. Added entries are matched by path, [folded](options::Options::ignore_case) as when
+/// linking
+///
+/// # Errors
+///
+/// See [`link`](crate::link)
+pub fn link_three(
+    base: entry::Directory,
+    left: entry::Directory,
+    right: entry::Directory,
+    options: &options::Options,
+) -> Result<Merge, super::Error> {
+    let pool = thread::pool()?;
+    merge_with(base, left, right, options, &pool)
+}
+
+fn merge_with(
+    base: entry::Directory,
+    left: entry::Directory,
+    right: entry::Directory,
+    options: &options::Options,
+    pool: &rayon::ThreadPool,
+) -> Result<Merge, super::Error> {
+    // Linking resets the statuses of `base`, so what `left` made of it is read before relinking
+    let (base, left) = super::link_with(base, left, options, pool)?;
+    let (to_left, from_left) = resolve(&base.entries, &left.entries);
+    let (base, right) = super::link_with(base, right, options, pool)?;
+    let (to_right, from_right) = resolve(&base.entries, &right.entries);
+
+    log::info!("Starting merge");
+    let start = std::time::Instant::now();
+
+    let fold = names::folds(options);
+    let key = |path: &std::path::Path| {
+        if fold {
+            names::fold(path, options)
+        } else {
+            path.to_path_buf()
+        }
+    };
+
+    let mut changes = base
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let (l, r) = (to_left[i], to_right[i]);
+            let outcome = outcome(
+                entry,
+                l.map(|l| &left.entries[l]),
+                r.map(|r| &right.entries[r]),
+            );
+            Change {
+                base: Some(i),
+                left: l,
+                right: r,
+                outcome,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut added = std::collections::HashMap::new();
+    for (i, entry) in left
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !from_left[*i])
+    {
+        added.insert(key(&entry.path), changes.len());
+        changes.push(Change {
+            base: None,
+            left: Some(i),
+            right: None,
+            outcome: Outcome::Left,
+        });
+    }
+    for (i, entry) in right
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !from_right[*i])
+    {
+        if let Some(&c) = added.get(&key(&entry.path)) {
+            let change: &mut Change = &mut changes[c];
+            change.right = Some(i);
+            change.outcome = match change.left {
+                Some(l) if !differs(&left.entries[l], entry) => Outcome::Both,
+                _ => Outcome::Conflict(Conflict::Added),
+            };
+        } else {
+            changes.push(Change {
+                base: None,
+                left: None,
+                right: Some(i),
+                outcome: Outcome::Right,
+            });
+        }
+    }
+
+    collide(
+        &mut changes,
+        (&base.entries, &left.entries, &right.entries),
+        key,
+    );
+
+    let path = |change: &Change| {
+        change
+            .base
+            .map(|i| &base.entries[i])
+            .or(change.left.map(|i| &left.entries[i]))
+            .or(change.right.map(|i| &right.entries[i]))
+            .map(|entry| entry.path.clone())
+    };
+    changes.sort_by_cached_key(path);
+
+    log::info!(
+        "Finished merging {} changes in {:?}",
+        changes.len(),
+        start.elapsed()
+    );
+    Ok(Merge {
+        base,
+        left,
+        right,
+        changes,
+    })
+}
+
+/// Combines what each side made of an entry of `base`, `None` if it deleted it
+fn outcome(
+    base: &entry::Entry,
+    left: Option<&entry::Entry>,
+    right: Option<&entry::Entry>,
+) -> Outcome {
+    let left_changed = left.is_none_or(|left| differs(base, left));
+    let right_changed = right.is_none_or(|right| differs(base, right));
+    match (left_changed, right_changed) {
+        (false, false) => Outcome::Unchanged,
+        (true, false) => Outcome::Left,
+        (false, true) => Outcome::Right,
+        (true, true) => match (left, right) {
+            (None, None) => Outcome::Both,
+            (Some(left), Some(right)) if !differs(left, right) => Outcome::Both,
+            (Some(left), Some(right))
+                if combines(base, left, right) || combines(base, right, left) =>
+            {
+                Outcome::Combined
+            }
+            (Some(_), Some(_)) => Outcome::Conflict(Conflict::Modified),
+            _ => Outcome::Conflict(Conflict::Deleted),
+        },
+    }
+}
+
+/// Turns the changes that are fine on their own but move different entries to the same path into
+/// conflicts
+fn collide(
+    changes: &mut [Change],
+    (base, left, right): (&[entry::Entry], &[entry::Entry], &[entry::Entry]),
+    key: impl Fn(&std::path::Path) -> std::path::PathBuf,
+) {
+    let mut landing = std::collections::HashMap::<_, Vec<_>>::new();
+    for (c, change) in changes.iter().enumerate() {
+        let landed = match change.outcome {
+            Outcome::Unchanged => change.base.map(|i| (&base[i].path, &base[i])),
+            Outcome::Left | Outcome::Both => change.left.map(|i| (&left[i].path, &left[i])),
+            Outcome::Right => change.right.map(|i| (&right[i].path, &right[i])),
+            // At the path of the moved side, with the content of the other
+            Outcome::Combined => {
+                change
+                    .base
+                    .zip(change.left)
+                    .zip(change.right)
+                    .map(|((b, l), r)| {
+                        if combines(&base[b], &left[l], &right[r]) {
+                            (&left[l].path, &right[r])
+                        } else {
+                            (&right[r].path, &left[l])
+                        }
+                    })
+            }
+            Outcome::Conflict(_) => None,
+        };
+        if let Some((path, result)) = landed {
+            landing.entry(key(path)).or_default().push((c, result));
+        }
+    }
+    for landed in landing.into_values().filter(|landed| {
+        landed
+            .iter()
+            .any(|(_, result)| differs(result, landed[0].1))
+    }) {
+        for (c, _) in landed {
+            changes[c].outcome = Outcome::Conflict(Conflict::Path);
+        }
+    }
+}
+
+/// Finds what became of each entry of `base` on the `other` side it was just linked with, and
+/// which entries of `other` came from `base` at all
+///
+/// Linked pairs are followed first, then the entries of `base` that are not linked, such as the
+/// ones whose type changed, take the entry left at their path if no other entry took it
+fn resolve(base: &[entry::Entry], other: &[entry::Entry]) -> (Vec<Option<usize>>, Vec<bool>) {
+    let mut claimed = vec![false; other.len()];
+    let mut resolved = base
+        .iter()
+        .map(|entry| match entry.status {
+            entry::Status::Same(i)
+            | entry::Status::Moved(i)
+            | entry::Status::Modified(i, _)
+            | entry::Status::Appended(i, _)
+            | entry::Status::Truncated(i, _) => Some(i),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for &i in resolved.iter().flatten() {
+        claimed[i] = true;
+    }
+
+    let paths = other
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (entry.path.as_path(), i))
+        .collect::<std::collections::HashMap<_, _>>();
+    for (entry, resolved) in base.iter().zip(&mut resolved) {
+        if resolved.is_none()
+            && let Some(&i) = paths.get(entry.path.as_path())
+            && !claimed[i]
+        {
+            claimed[i] = true;
+            *resolved = Some(i);
+        }
+    }
+
+    (resolved, claimed)
+}
+
+/// Whether `moved` only moved the entry of `base` and `edited` only changed its content
+fn combines(base: &entry::Entry, moved: &entry::Entry, edited: &entry::Entry) -> bool {
+    moved.path != base.path
+        && moved.kind == base.kind
+        && moved.hash == base.hash
+        && edited.path == base.path
+        && edited.kind == base.kind
+        && edited.hash != base.hash
+}
+
+/// Whether two entries differ by path, type or content
+fn differs(a: &entry::Entry, b: &entry::Entry) -> bool {
+    a.path != b.path || a.kind != b.kind || a.hash != b.hash
+}
@@ -0,0 +1,420 @@
+use super::{algorithm, entry, options};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not read mtree spec: {0}")]
+    Read(std::io::Error),
+    #[error("Could not write mtree spec: {0}")]
+    Write(std::io::Error),
+    #[error("Malformed mtree spec on line {0}")]
+    Malformed(usize),
+    #[error("Unexpected `..` on line {0}, already at the root")]
+    Root(usize),
+    #[error("Duplicate mtree entry for `{0}`")]
+    Duplicate(std::path::PathBuf),
+}
+
+/// An entry of the spec, with the keywords that apply to it
+struct Item {
+    path: std::path::PathBuf,
+    keywords: std::collections::HashMap<String, String>,
+}
+
+/// Builds a [`Directory`](entry::Directory) rooted at `path` from a BSD mtree specification, as
+/// written by `mtree -c` or `bsdtar --format=mtree`
+///
+/// Both the hierarchical and the full path forms are understood, along with `/set` and `/unset`.
+/// The `type`, `size`, `time`, `mode`, `uid` and `gid` keywords are mapped onto the entries, and
+/// their hashes are taken from the `sha256digest` keywords if every file has a valid one, or from
+/// the `md5digest` ones. Files and symlinks without a valid digest of the chosen kind cannot be
+/// compared and are skipped, as are devices, FIFOs and sockets. Symlinks are compared by the
+/// content they point to, so the `link` keywords written by [`write_mtree`] are ignored
+///
+/// # Errors
+///
+/// If the spec cannot be read, a line is malformed, or a path is described twice
+pub fn read_mtree(
+    reader: impl std::io::BufRead,
+    path: std::path::PathBuf,
+) -> Result<entry::Directory, super::Error> {
+    let items = parse(reader)?;
+
+    let files = items
+        .iter()
+        .filter(|item| kind(item) == Some(entry::Kind::File))
+        .collect::<Vec<_>>();
+    let hashed = |item: &&Item, algorithm| {
+        digest(item, algorithm)
+            .and_then(|digest| hash(digest, algorithm))
+            .is_some()
+    };
+    let algorithm = if !files.is_empty()
+        && files
+            .iter()
+            .all(|item| hashed(item, algorithm::Algorithm::Sha256))
+    {
+        algorithm::Algorithm::Sha256
+    } else if files
+        .iter()
+        .any(|item| hashed(item, algorithm::Algorithm::Md5))
+    {
+        algorithm::Algorithm::Md5
+    } else {
+        // Nothing to compare either way, so a spec without any digest is no reason to switch
+        algorithm::Algorithm::default()
+    };
+
+    let mut entries = Vec::new();
+    let mut directories = std::collections::BTreeSet::new();
+    for item in items {
+        let Some(kind) = kind(&item) else {
+            log::warn!(
+                "Skipping `{}`, its type is not supported",
+                item.path.display()
+            );
+            continue;
+        };
+
+        if kind == entry::Kind::Directory {
+            if !item.path.as_os_str().is_empty() {
+                directories.insert(item.path);
+            }
+            continue;
+        }
+
+        let Some(digest) = digest(&item, algorithm) else {
+            log::warn!(
+                "Skipping `{}`, it has no {algorithm} digest",
+                item.path.display()
+            );
+            continue;
+        };
+        let Some(hash) = hash(digest, algorithm) else {
+            log::warn!(
+                "Skipping `{}`, its {algorithm} digest `{digest}` is malformed",
+                item.path.display()
+            );
+            continue;
+        };
+
+        let size = item
+            .keywords
+            .get("size")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or_default();
+        let modified = item.keywords.get("time").and_then(|time| parse_time(time));
+
+        directories.extend(
+            item.path
+                .ancestors()
+                .skip(1)
+                .filter(|a| !a.as_os_str().is_empty())
+                .map(std::path::Path::to_path_buf),
+        );
+        let number = |key, radix| {
+            item.keywords
+                .get(key)
+                .and_then(|value| u32::from_str_radix(value, radix).ok())
+        };
+        let mut entry = entry::Entry::new(item.path, hash, None, kind, size, modified, None);
+        entry.mode = number("mode", 8);
+        entry.uid = number("uid", 10);
+        entry.gid = number("gid", 10);
+        entries.push(entry);
+    }
+
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].path == pair[1].path) {
+        return Err(Error::Duplicate(pair[0].path.clone()).into());
+    }
+    entries.sort_unstable();
+
+    let settings = options::Settings {
+        algorithm,
+        ..options::Settings::default()
+    };
+    let mut directory =
+        entry::Directory::new(path, entries, directories.into_iter().collect(), settings);
+    directory.live = false;
+    Ok(directory)
+}
+
+/// Writes the `directory` as a hierarchical BSD mtree specification, which `mtree -f` can check
+/// from within the directory
+///
+/// Files carry their `size`, `time`, `mode`, `uid` and `gid` when known, and their raw hash as a
+/// `md5digest` or `sha256digest`. Symlinks carry their target as well when the `directory` is
+/// still on disk to read it from
+///
+/// # Errors
+///
+/// If the spec cannot be written
+pub fn write_mtree(
+    directory: &entry::Directory,
+    mut writer: impl std::io::Write,
+) -> Result<(), super::Error> {
+    let mut children = std::collections::BTreeMap::<&std::path::Path, (Vec<_>, Vec<_>)>::new();
+    for entry in directory.entries() {
+        let parent = entry.path.parent().unwrap_or(std::path::Path::new(""));
+        children.entry(parent).or_default().0.push(entry);
+    }
+    for path in directory.directories() {
+        let parent = path.parent().unwrap_or(std::path::Path::new(""));
+        children.entry(parent).or_default().1.push(path.as_path());
+    }
+
+    writeln!(writer, "#mtree").map_err(Error::Write)?;
+    writeln!(writer, ". type=dir").map_err(Error::Write)?;
+    write_children(
+        directory,
+        &children,
+        std::path::Path::new(""),
+        1,
+        &mut writer,
+    )?;
+    writeln!(writer, "..").map_err(Error::Write)?;
+
+    writer.flush().map_err(Error::Write)?;
+    Ok(())
+}
+
+/// Files of the directory first, then each subdirectory followed by its content, as `mtree -c`
+/// lays them out
+fn write_children(
+    directory: &entry::Directory,
+    children: &std::collections::BTreeMap<
+        &std::path::Path,
+        (Vec<&entry::Entry>, Vec<&std::path::Path>),
+    >,
+    parent: &std::path::Path,
+    depth: usize,
+    writer: &mut impl std::io::Write,
+) -> Result<(), Error> {
+    let Some((entries, subdirectories)) = children.get(parent) else {
+        return Ok(());
+    };
+    let indent = "    ".repeat(depth);
+
+    let mut entries = entries.clone();
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    for entry in entries {
+        let name = encode(entry.path.file_name().unwrap_or_default());
+        let digest = match directory.settings().algorithm {
+            algorithm::Algorithm::Md5 => "md5digest",
+            algorithm::Algorithm::Sha256 => "sha256digest",
+        };
+
+        write!(writer, "{indent}{name}").map_err(Error::Write)?;
+        if entry.kind == entry::Kind::Symlink {
+            write!(writer, " type=link").map_err(Error::Write)?;
+            if let Ok(target) = std::fs::read_link(directory.path().join(&entry.path)) {
+                write!(writer, " link={}", encode(target.as_os_str())).map_err(Error::Write)?;
+            }
+        } else {
+            write!(writer, " type=file").map_err(Error::Write)?;
+        }
+        write!(writer, " size={}", entry.size).map_err(Error::Write)?;
+        if let Some(modified) = entry
+            .modified
+            .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+        {
+            write!(
+                writer,
+                " time={}.{:09}",
+                modified.as_secs(),
+                modified.subsec_nanos()
+            )
+            .map_err(Error::Write)?;
+        }
+        if let Some(mode) = entry.mode {
+            write!(writer, " mode={mode:04o}").map_err(Error::Write)?;
+        }
+        if let Some(uid) = entry.uid {
+            write!(writer, " uid={uid}").map_err(Error::Write)?;
+        }
+        if let Some(gid) = entry.gid {
+            write!(writer, " gid={gid}").map_err(Error::Write)?;
+        }
+        writeln!(writer, " {digest}={:x}", entry.raw_hash()).map_err(Error::Write)?;
+    }
+
+    for subdirectory in subdirectories {
+        let name = encode(subdirectory.file_name().unwrap_or_default());
+        writeln!(writer, "{indent}{name} type=dir").map_err(Error::Write)?;
+        write_children(directory, children, subdirectory, depth + 1, writer)?;
+        writeln!(writer, "{indent}..").map_err(Error::Write)?;
+    }
+
+    Ok(())
+}
+
+/// Reads every entry of the spec with its keywords resolved, in order
+fn parse(reader: impl std::io::BufRead) -> Result<Vec<Item>, Error> {
+    let mut defaults = std::collections::HashMap::<String, String>::new();
+    let mut current = std::path::PathBuf::new();
+    let mut depth = 0_usize;
+    let mut items = Vec::new();
+
+    let mut pending = String::new();
+    for (number, line) in reader.lines().enumerate() {
+        let number = number + 1;
+        let line = line.map_err(Error::Read)?;
+
+        // A trailing backslash continues the entry on the next line
+        if let Some(line) = line.strip_suffix('\\') {
+            pending.push_str(line);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(&line);
+        let line = std::mem::take(&mut pending);
+
+        let mut words = line.split_whitespace();
+        let Some(first) = words.next() else {
+            continue;
+        };
+        if first.starts_with('#') {
+            continue;
+        }
+
+        match first {
+            "/set" => {
+                for word in words {
+                    let (key, value) = word.split_once('=').ok_or(Error::Malformed(number))?;
+                    defaults.insert(String::from(key), String::from(value));
+                }
+                continue;
+            }
+            "/unset" => {
+                for key in words {
+                    if key == "all" {
+                        defaults.clear();
+                    } else {
+                        defaults.remove(key);
+                    }
+                }
+                continue;
+            }
+            ".." => {
+                depth = depth.checked_sub(1).ok_or(Error::Root(number))?;
+                current.pop();
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut keywords = defaults.clone();
+        for word in words {
+            let (key, value) = word.split_once('=').ok_or(Error::Malformed(number))?;
+            keywords.insert(String::from(key), String::from(value));
+        }
+
+        let name = decode(first).ok_or(Error::Malformed(number))?;
+        // Names with a slash are full paths and leave the current directory alone
+        let full = name.contains('/');
+        let path = if full {
+            std::path::PathBuf::from(&name)
+        } else {
+            current.join(&name)
+        };
+        let path = path
+            .components()
+            .filter(|c| !matches!(c, std::path::Component::CurDir))
+            .collect::<std::path::PathBuf>();
+
+        if !full && keywords.get("type").is_some_and(|t| t == "dir") {
+            depth += 1;
+            if name != "." {
+                current.push(&name);
+            }
+        }
+
+        items.push(Item { path, keywords });
+    }
+
+    Ok(items)
+}
+
+fn kind(item: &Item) -> Option<entry::Kind> {
+    match item.keywords.get("type").map_or("file", String::as_str) {
+        "file" => Some(entry::Kind::File),
+        "dir" => Some(entry::Kind::Directory),
+        "link" => Some(entry::Kind::Symlink),
+        _ => None,
+    }
+}
+
+fn digest(item: &Item, algorithm: algorithm::Algorithm) -> Option<&str> {
+    let keys = match algorithm {
+        algorithm::Algorithm::Md5 => ["md5digest", "md5"],
+        algorithm::Algorithm::Sha256 => ["sha256digest", "sha256"],
+    };
+    keys.iter()
+        .find_map(|key| item.keywords.get(*key))
+        .map(String::as_str)
+}
+
+/// Parses a digest, `None` unless it is hexadecimal and as long as the `algorithm` produces
+fn hash(digest: &str, algorithm: algorithm::Algorithm) -> Option<entry::Hash> {
+    digest
+        .parse::<entry::Hash>()
+        .ok()
+        .filter(|hash| hash.as_bytes().len() == algorithm.length())
+}
+
+/// Parses `SECONDS.NANOSECONDS`, the fraction being optional
+fn parse_time(time: &str) -> Option<std::time::SystemTime> {
+    let (seconds, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let seconds = seconds.parse::<u64>().ok()?;
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{fraction:0<9}").parse::<u32>().ok()?;
+    std::time::UNIX_EPOCH.checked_add(std::time::Duration::new(seconds, nanos))
+}
+
+/// Decodes a name encoded with `vis(3)`, octal escapes and C-style escapes alike
+fn decode(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut input = name.bytes();
+    while let Some(byte) = input.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let escaped = input.next()?;
+        let decoded = match escaped {
+            b'0'..=b'7' => {
+                let high = input.next()?;
+                let low = input.next()?;
+                let digits = [escaped, high, low];
+                u8::from_str_radix(std::str::from_utf8(&digits).ok()?, 8).ok()?
+            }
+            b'\\' => b'\\',
+            b's' => b' ',
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b'#' => b'#',
+            _ => return None,
+        };
+        bytes.push(decoded);
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Encodes a name with the octal escapes of `vis(3)`, keeping it a single word
+fn encode(name: &std::ffi::OsStr) -> String {
+    use std::fmt::Write;
+
+    let mut encoded = String::new();
+    for byte in name.as_encoded_bytes() {
+        if byte.is_ascii_graphic() && !matches!(byte, b'\\' | b'#') {
+            encoded.push(char::from(*byte));
+        } else {
+            let _ = write!(encoded, "\\{byte:03o}");
+        }
+    }
+    encoded
+}
@@ -0,0 +1,72 @@
+use super::{decompress, entry, options};
+
+/// Folded forms of the entry paths of both sides, used to match paths that differ only by Unicode
+/// normalization, case or a compression extension
+pub struct Names {
+    pub left: Vec<std::path::PathBuf>,
+    pub right: Vec<std::path::PathBuf>,
+}
+
+impl Names {
+    pub fn new(
+        left: &[entry::Entry],
+        right: &[entry::Entry],
+        options: &options::Options,
+        pool: &rayon::ThreadPool,
+    ) -> Option<Self> {
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+        if !folds(options) {
+            return None;
+        }
+
+        log::info!("Folding names");
+        let start = std::time::Instant::now();
+
+        let names = pool.install(|| {
+            let fold = |entries: &[entry::Entry]| {
+                entries.par_iter().map(|e| fold(&e.path, options)).collect()
+            };
+
+            Self {
+                left: fold(left),
+                right: fold(right),
+            }
+        });
+
+        log::info!("Finished folding names in {:?}", start.elapsed());
+        Some(names)
+    }
+}
+
+/// Whether the `options` match paths by their folded forms at all
+pub fn folds(options: &options::Options) -> bool {
+    options.unicode || options.ignore_case || !options.decompressions.is_empty()
+}
+
+// Canonically equivalent strings share the same NFC form, so NFD names coming back from macOS
+// match their NFC originals
+pub fn fold(path: &std::path::Path, options: &options::Options) -> std::path::PathBuf {
+    use unicode_normalization::UnicodeNormalization;
+
+    // Compressed files are hashed by their decompressed content, and named after it
+    let decompressed = decompress::find(path, &options.decompressions).map(|(_, path)| path);
+    let path = decompressed.as_deref().unwrap_or(path);
+
+    // Names that are not valid UTF-8 cannot be normalized and are matched as they are
+    let Some(name) = path.to_str() else {
+        return path.to_path_buf();
+    };
+
+    let name = if options.unicode {
+        name.nfc().collect()
+    } else {
+        String::from(name)
+    };
+
+    if options.ignore_case {
+        std::path::PathBuf::from(name.to_lowercase())
+    } else {
+        std::path::PathBuf::from(name)
+    }
+}
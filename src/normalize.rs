@@ -0,0 +1,248 @@
+/// Canonicalizes the content of the text files matching [`glob`](Self::glob) before hashing
+#[derive(Debug, Clone)]
+pub struct Normalization {
+    pub glob: glob::Pattern,
+    pub rules: Rules,
+}
+
+/// Which canonicalizations are applied by a [`Normalization`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Rules {
+    /// Replace `\r\n` and lone `\r` with `\n`
+    pub line_endings: bool,
+    /// Remove spaces and tabs at the end of every line
+    pub trailing_whitespace: bool,
+    /// Remove a leading UTF-8 byte order mark
+    pub bom: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            line_endings: true,
+            trailing_whitespace: true,
+            bom: true,
+        }
+    }
+}
+
+const BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
+/// Streaming implementation of the [`Rules`], fed one buffer at a time
+pub struct Normalizer {
+    rules: Rules,
+    // The leading bytes held back while they still look like a byte order mark
+    head: Option<Vec<u8>>,
+    whitespace: Vec<u8>,
+    carriage_return: bool,
+    changed: bool,
+}
+
+impl Normalizer {
+    pub fn new(rules: Rules) -> Self {
+        Self {
+            rules,
+            head: rules.bom.then(|| Vec::with_capacity(BOM.len())),
+            whitespace: Vec::new(),
+            carriage_return: false,
+            changed: false,
+        }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8], out: &mut impl FnMut(&[u8])) {
+        if let Some(head) = &mut self.head {
+            let missing = (BOM.len() - head.len()).min(bytes.len());
+            head.extend_from_slice(&bytes[..missing]);
+            bytes = &bytes[missing..];
+
+            if !BOM.starts_with(head) {
+                if let Some(head) = self.head.take() {
+                    self.process(&head, out);
+                }
+            } else if head.len() == BOM.len() {
+                self.head = None;
+                self.changed = true;
+            } else {
+                return;
+            }
+        }
+
+        self.process(bytes, out);
+    }
+
+    /// Flushes the held back bytes, returning whether the content was altered at all
+    pub fn finish(mut self, out: &mut impl FnMut(&[u8])) -> bool {
+        if let Some(head) = self.head.take() {
+            self.process(&head, out);
+        }
+
+        if self.carriage_return {
+            self.changed = true;
+            out(b"\n");
+        }
+
+        if !self.whitespace.is_empty() {
+            self.changed = true;
+        }
+
+        self.changed
+    }
+
+    fn process(&mut self, bytes: &[u8], out: &mut impl FnMut(&[u8])) {
+        let mut start = 0;
+
+        for (i, byte) in bytes.iter().copied().enumerate() {
+            if self.carriage_return {
+                self.carriage_return = false;
+                self.changed = true;
+                out(b"\n");
+                if byte == b'\n' {
+                    start = i + 1;
+                    continue;
+                }
+            }
+
+            match byte {
+                b'\r' if self.rules.line_endings => {
+                    flush(&bytes[start..i], out);
+                    self.end_line();
+                    self.carriage_return = true;
+                    start = i + 1;
+                }
+                // Kept as is, but still ends the line when line endings are not normalized
+                b'\r' | b'\n' => {
+                    flush(&bytes[start..i], out);
+                    self.end_line();
+                    start = i;
+                }
+                b' ' | b'\t' if self.rules.trailing_whitespace => {
+                    flush(&bytes[start..i], out);
+                    self.whitespace.push(byte);
+                    start = i + 1;
+                }
+                _ => {
+                    if !self.whitespace.is_empty() {
+                        flush(&bytes[start..i], out);
+                        out(&self.whitespace);
+                        self.whitespace.clear();
+                        start = i;
+                    }
+                }
+            }
+        }
+
+        flush(&bytes[start..], out);
+    }
+
+    fn end_line(&mut self) {
+        if !self.whitespace.is_empty() {
+            self.whitespace.clear();
+            self.changed = true;
+        }
+    }
+}
+
+fn flush(bytes: &[u8], out: &mut impl FnMut(&[u8])) {
+    if !bytes.is_empty() {
+        out(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: Rules = Rules {
+        line_endings: true,
+        trailing_whitespace: true,
+        bom: true,
+    };
+    const NONE: Rules = Rules {
+        line_endings: false,
+        trailing_whitespace: false,
+        bom: false,
+    };
+
+    fn normalize(chunks: &[&[u8]], rules: Rules) -> (Vec<u8>, bool) {
+        let mut output = Vec::new();
+        let mut normalizer = Normalizer::new(rules);
+        for chunk in chunks {
+            normalizer.update(chunk, &mut |bytes| output.extend_from_slice(bytes));
+        }
+        let changed = normalizer.finish(&mut |bytes| output.extend_from_slice(bytes));
+        (output, changed)
+    }
+
+    /// Checks the content is normalized the same whole, split at every boundary, and fed one
+    /// byte at a time
+    fn check(input: &[u8], rules: Rules, expected: &[u8]) {
+        let whole = normalize(&[input], rules);
+        assert_eq!(
+            whole,
+            (expected.to_vec(), input != expected),
+            "{:?} with {rules:?}",
+            String::from_utf8_lossy(input)
+        );
+
+        for i in 0..=input.len() {
+            let (head, tail) = input.split_at(i);
+            assert_eq!(
+                normalize(&[head, tail], rules),
+                whole,
+                "{:?} split at {i}",
+                String::from_utf8_lossy(input)
+            );
+        }
+
+        let bytes = input.chunks(1).collect::<Vec<_>>();
+        assert_eq!(normalize(&bytes, rules), whole);
+    }
+
+    #[test]
+    fn removes_byte_order_marks() {
+        let bom = Rules { bom: true, ..NONE };
+        check(b"\xef\xbb\xbfa\n", bom, b"a\n");
+        check(b"\xef\xbb\xbf", bom, b"");
+        check(b"\xef\xbbx", bom, b"\xef\xbbx");
+        check(b"\xef\xbb", bom, b"\xef\xbb");
+        check(b"a\xef\xbb\xbf", bom, b"a\xef\xbb\xbf");
+        check(b"\xef\xbb\xbfa", NONE, b"\xef\xbb\xbfa");
+    }
+
+    #[test]
+    fn normalizes_line_endings() {
+        let endings = Rules {
+            line_endings: true,
+            ..NONE
+        };
+        check(b"a\r\nb\r\n", endings, b"a\nb\n");
+        check(b"a\rb\r", endings, b"a\nb\n");
+        check(b"a\r\r\nb", endings, b"a\n\nb");
+        check(b"a\n\rb", endings, b"a\n\nb");
+        check(b"a \r\n", endings, b"a \n");
+        check(b"a\r\nb", NONE, b"a\r\nb");
+    }
+
+    #[test]
+    fn removes_trailing_whitespace() {
+        let whitespace = Rules {
+            trailing_whitespace: true,
+            ..NONE
+        };
+        check(b"a \t\nb", whitespace, b"a\nb");
+        check(b"a b\t c\n", whitespace, b"a b\t c\n");
+        check(b"a  ", whitespace, b"a");
+        check(b"  \n  \n", whitespace, b"\n\n");
+        check(b"a \r\nb\t\r\n", whitespace, b"a\r\nb\r\n");
+        check(b"a \rb", whitespace, b"a\rb");
+        check(b"a \n", NONE, b"a \n");
+    }
+
+    #[test]
+    fn combines_rules() {
+        check(b"\xef\xbb\xbfa \r\nb\t\rc  ", ALL, b"a\nb\nc");
+        check(b"a\r\n b\n", ALL, b"a\n b\n");
+        check(b"plain\ntext\n", ALL, b"plain\ntext\n");
+        check(b"", ALL, b"");
+    }
+}
@@ -0,0 +1,92 @@
+/// Controls what is collected while indexing and what is checked while linking
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// The digest used to hash file contents
+    pub algorithm: super::Algorithm,
+    /// Read the extended attributes (including POSIX ACLs) of every file and flag linked pairs
+    /// whose attribute sets differ
+    pub attributes: bool,
+    /// Flag linked pairs whose mode, user or group differ, as far as both sides know them
+    pub permissions: bool,
+    /// Check whether one side of each `Modified` pair is a byte prefix of the other, reporting
+    /// them as `Appended` or `Truncated` instead
+    pub prefixes: bool,
+    /// Score how similar the contents of each `Modified` pair are, using content-defined
+    /// chunking
+    pub similarity: bool,
+    /// Match paths that differ only by Unicode normalization, such as NFD names coming back from
+    /// macOS, flagging the linked pairs
+    pub unicode: bool,
+    /// Match paths that differ only by case, flagging the linked pairs
+    pub ignore_case: bool,
+    /// Normalize the content of matching text files before hashing, flagging the linked pairs
+    /// that are only equal after normalization
+    ///
+    /// The first [`Normalization`](crate::Normalization) whose glob matches the path relative to
+    /// the compared directory is applied
+    pub normalizations: Vec<super::Normalization>,
+    /// Hash the canonical form of matching files instead of their content, flagging the linked
+    /// pairs that are only equal after canonicalization
+    ///
+    /// Takes precedence over [`normalizations`](Self::normalizations). Files that cannot be
+    /// canonicalized, such as invalid JSON, are hashed by their content with a warning
+    pub canonicalizations: Vec<super::Canonicalization>,
+    /// Hash the decompressed content of compressed files instead of their bytes, and match them
+    /// with the files named like their decompressed content, e.g. `foo.log.gz` with `foo.log`
+    ///
+    /// The first [`Decompression`](crate::Decompression) whose extension ends the name is applied,
+    /// and [`normalizations`](Self::normalizations) and
+    /// [`canonicalizations`](Self::canonicalizations) match the name without it. Files that cannot
+    /// be decompressed are hashed as they are with a warning
+    pub decompressions: Vec<super::Decompression>,
+    /// Path to a persistent cache of file hashes, reused as long as the files are unchanged
+    ///
+    /// [`verify`](crate::verify) never reads from the cache, since it has to read every file
+    pub cache: Option<std::path::PathBuf>,
+    /// Hash every file again instead of reading from the [`cache`](Self::cache), while still
+    /// updating it
+    pub rehash: bool,
+    /// Index the members of archives found in the tree, and in other archives, as the content of
+    /// a directory named after the archive instead of hashing the archive itself
+    ///
+    /// Members are not on disk, so the checks that read file contents skip them. Archives that
+    /// cannot be read are hashed as plain files with a warning, and so are nested archives larger
+    /// than 256 MiB, since they have to be read into memory to be expanded
+    pub into_archives: bool,
+}
+
+/// The [`Options`] that affect the hashes of a [`Directory`](crate::Directory), recorded along
+/// with it so that directories indexed differently can be told apart
+#[derive(Debug, Default, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Settings {
+    pub algorithm: super::Algorithm,
+    pub attributes: bool,
+    pub normalizations: Vec<String>,
+    pub canonicalizations: Vec<String>,
+    pub decompressions: Vec<String>,
+}
+
+impl From<&Options> for Settings {
+    fn from(options: &Options) -> Self {
+        Self {
+            algorithm: options.algorithm,
+            attributes: options.attributes,
+            normalizations: options
+                .normalizations
+                .iter()
+                .map(|n| format!("{}:{:?}", n.glob, n.rules))
+                .collect(),
+            canonicalizations: options
+                .canonicalizations
+                .iter()
+                .map(|c| format!("{}={:?}", c.glob, c.canonicalizer))
+                .collect(),
+            decompressions: options
+                .decompressions
+                .iter()
+                .map(|d| format!("{}={}", d.extension, d.format))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use super::{algorithm::Algorithm, entry::Hash};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Could not open file {0}: {1}")]
+    CannotOpen(std::path::PathBuf, std::io::Error),
+    #[error("Could not read file {0}: {1}")]
+    CannotRead(std::path::PathBuf, std::io::Error),
+}
+
+/// Random values mixed into the rolling hash, one per byte value
+const GEAR: [u64; 256] = gear();
+
+/// Scores how much of the content of the files at `left` and `right` is shared, in percent
+///
+/// Both files are split into content-defined chunks, so that an insertion or a deletion only
+/// changes the chunks around it instead of shifting every block after it. The score is the share
+/// of bytes, over both files, that lie in chunks found on both sides
+///
+/// # Errors
+///
+/// If either file cannot be read
+pub fn similarity(left: &std::path::Path, right: &std::path::Path) -> Result<u8, super::Error> {
+    let size = |path: &std::path::Path| {
+        std::fs::metadata(path)
+            .map(|m| m.len())
+            .map_err(|e| Error::CannotOpen(path.to_path_buf(), e))
+    };
+    let chunker = Chunker::new(size(left)?.max(size(right)?));
+    let chunks = |path: &std::path::Path| {
+        let file =
+            std::fs::File::open(path).map_err(|e| Error::CannotOpen(path.to_path_buf(), e))?;
+        chunker
+            .chunks(std::io::BufReader::new(file))
+            .map_err(|e| Error::CannotRead(path.to_path_buf(), e))
+    };
+
+    Ok(score(&chunks(left)?, &chunks(right)?))
+}
+
+/// The number of bytes held by the chunks of each digest, along with the total size
+type Chunks = (std::collections::HashMap<Hash, u64>, u64);
+
+fn score((left, left_size): &Chunks, (right, right_size): &Chunks) -> u8 {
+    if left_size + right_size == 0 {
+        return 100;
+    }
+
+    let shared = left
+        .iter()
+        .filter_map(|(digest, size)| right.get(digest).map(|other| size.min(other)))
+        .sum::<u64>();
+
+    // Bounded by 100 since `shared` is at most the smaller of both sizes
+    u8::try_from(shared * 200 / (left_size + right_size)).unwrap_or(100)
+}
+
+/// Splits content at the positions where a rolling hash of the last bytes matches a mask
+struct Chunker {
+    mask: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Chunker {
+    /// Aims at around 64 chunks for the larger file, within sensible bounds
+    fn new(size: u64) -> Self {
+        let average = (size / 64).next_power_of_two().clamp(64, 64 * 1024);
+        Self {
+            mask: average - 1,
+            min: average / 4,
+            max: average * 8,
+        }
+    }
+
+    /// Splits the content of `reader`, digesting chunks with MD5 so that they compare the same
+    /// whatever the buffer boundaries, the platform or the compiler
+    fn chunks(&self, mut reader: impl std::io::BufRead) -> std::io::Result<Chunks> {
+        let mut chunks = std::collections::HashMap::new();
+        let mut digest = Algorithm::Md5.hasher();
+        let mut fingerprint = 0_u64;
+        let mut length = 0;
+        let mut total = 0;
+
+        loop {
+            let buffer = reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+
+            let mut start = 0;
+            for (i, byte) in buffer.iter().enumerate() {
+                fingerprint = (fingerprint << 1).wrapping_add(GEAR[usize::from(*byte)]);
+                length += 1;
+
+                if (length >= self.min && fingerprint & self.mask == 0) || length >= self.max {
+                    digest.update(&buffer[start..=i]);
+                    let chunk = std::mem::replace(&mut digest, Algorithm::Md5.hasher());
+                    *chunks.entry(chunk.finalize()).or_default() += length;
+                    fingerprint = 0;
+                    length = 0;
+                    start = i + 1;
+                }
+            }
+            digest.update(&buffer[start..]);
+
+            let consumed = buffer.len();
+            total += consumed as u64;
+            reader.consume(consumed);
+        }
+
+        if length > 0 {
+            *chunks.entry(digest.finalize()).or_default() += length;
+        }
+
+        Ok((chunks, total))
+    }
+}
+
+const fn gear() -> [u64; 256] {
+    // SplitMix64, so the table is the same on every run and on both sides
+    let mut table = [0; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut i = 0;
+    while i < table.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunker, score};
+
+    /// Deterministic bytes that do not repeat, so that chunks only match where content does
+    fn noise(seed: u64, length: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state.to_le_bytes()[0]
+            })
+            .collect()
+    }
+
+    fn similarity(left: &[u8], right: &[u8]) -> u8 {
+        let chunker = Chunker::new(left.len().max(right.len()) as u64);
+        score(
+            &chunker.chunks(left).unwrap(),
+            &chunker.chunks(right).unwrap(),
+        )
+    }
+
+    #[test]
+    fn scores_identical_content_fully() {
+        let content = noise(1, 100_000);
+        assert_eq!(similarity(&content, &content), 100);
+        assert_eq!(similarity(b"", b""), 100);
+    }
+
+    #[test]
+    fn keeps_a_high_score_after_a_small_insertion() {
+        let left = noise(1, 100_000);
+        let mut right = left.clone();
+        right.insert(50_000, b'x');
+        assert!(similarity(&left, &right) >= 90);
+    }
+
+    #[test]
+    fn scores_a_rewrite_near_zero() {
+        assert!(similarity(&noise(1, 100_000), &noise(2, 100_000)) <= 5);
+    }
+
+    #[test]
+    fn digests_chunks_regardless_of_buffer_boundaries() {
+        let content = noise(1, 10_000);
+        let chunker = Chunker::new(content.len() as u64);
+        assert_eq!(
+            chunker.chunks(content.as_slice()).unwrap(),
+            chunker
+                .chunks(std::io::BufReader::with_capacity(7, content.as_slice()))
+                .unwrap()
+        );
+    }
+}
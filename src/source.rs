@@ -0,0 +1,351 @@
+use super::entry;
+
+/// What the crawler needs to know about a path, as reported by a [`FileSystem`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Metadata {
+    /// Whether the path is a file, a directory or a symlink
+    pub kind: entry::Kind,
+    /// The size of the content in bytes
+    pub size: u64,
+    pub modified: Option<std::time::SystemTime>,
+    /// When the metadata last changed, used with [`inode`](Self::inode) to tell whether a cached
+    /// hash is still valid
+    pub changed: Option<std::time::SystemTime>,
+    /// The device and inode numbers, identifying hardlinks to the same content
+    pub inode: Option<(u64, u64)>,
+    /// How many hardlinks point to the content
+    pub links: u64,
+    /// The permission bits, along with the setuid, setgid and sticky bits
+    pub mode: Option<u32>,
+    /// The user owning the path
+    pub uid: Option<u32>,
+    /// The group owning the path
+    pub gid: Option<u32>,
+}
+
+/// The files and directories a [`Directory`](entry::Directory) is indexed from
+///
+/// Paths are absolute within the file system, and the crawler only ever reads through these
+/// methods, so trees that are not on the local disk can be indexed and compared
+pub trait FileSystem: Send + Sync + 'static {
+    /// The content of an opened file
+    type File: std::io::Read + std::io::Seek + Send;
+
+    /// Lists the paths of the children of the directory at `path`
+    ///
+    /// # Errors
+    ///
+    /// If `path` is not a readable directory
+    fn read_dir(&self, path: &std::path::Path) -> std::io::Result<Vec<std::path::PathBuf>>;
+
+    /// Returns the metadata of `path`, following symlinks
+    ///
+    /// # Errors
+    ///
+    /// If `path` does not exist, or is a broken symlink
+    fn metadata(&self, path: &std::path::Path) -> std::io::Result<Metadata>;
+
+    /// Returns the metadata of `path` itself, even if it is a symlink
+    ///
+    /// # Errors
+    ///
+    /// If `path` does not exist
+    fn symlink_metadata(&self, path: &std::path::Path) -> std::io::Result<Metadata>;
+
+    /// Opens the file at `path` for reading, following symlinks
+    ///
+    /// # Errors
+    ///
+    /// If `path` is not a readable file
+    fn open(&self, path: &std::path::Path) -> std::io::Result<Self::File>;
+
+    /// Returns the extended attributes of `path`, following symlinks
+    ///
+    /// # Errors
+    ///
+    /// If the attributes cannot be read
+    fn attributes(&self, path: &std::path::Path) -> std::io::Result<entry::Attributes>;
+
+    /// Whether the paths are on the local disk, where the checks that read file contents again
+    /// after indexing can reach them
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// The local disk
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Local;
+
+impl FileSystem for Local {
+    type File = std::fs::File;
+
+    fn read_dir(&self, path: &std::path::Path) -> std::io::Result<Vec<std::path::PathBuf>> {
+        path.read_dir()?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &std::path::Path) -> std::io::Result<Metadata> {
+        path.metadata().map(|metadata| convert(&metadata, false))
+    }
+
+    fn symlink_metadata(&self, path: &std::path::Path) -> std::io::Result<Metadata> {
+        path.symlink_metadata()
+            .map(|metadata| convert(&metadata, true))
+    }
+
+    fn open(&self, path: &std::path::Path) -> std::io::Result<Self::File> {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(false)
+            .create(false)
+            .open(path)
+    }
+
+    fn attributes(&self, path: &std::path::Path) -> std::io::Result<entry::Attributes> {
+        xattr::list_deref(path)?
+            .filter_map(|name| {
+                xattr::get_deref(path, &name)
+                    .map(|value| value.map(|value| (name, value)))
+                    .transpose()
+            })
+            .collect::<Result<_, _>>()
+            .map(entry::Attributes::new)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(unix)]
+fn convert(metadata: &std::fs::Metadata, symlink: bool) -> Metadata {
+    use std::os::unix::fs::MetadataExt;
+
+    let time = |secs: i64, nsecs: i64| {
+        let offset = std::time::Duration::new(secs.unsigned_abs(), u32::try_from(nsecs).ok()?);
+        if secs < 0 {
+            std::time::UNIX_EPOCH.checked_sub(offset)
+        } else {
+            std::time::UNIX_EPOCH.checked_add(offset)
+        }
+    };
+    Metadata {
+        kind: kind(metadata, symlink),
+        size: metadata.size(),
+        modified: metadata.modified().ok(),
+        changed: time(metadata.ctime(), metadata.ctime_nsec()),
+        inode: Some((metadata.dev(), metadata.ino())),
+        links: metadata.nlink(),
+        mode: Some(metadata.mode() & 0o7777),
+        uid: Some(metadata.uid()),
+        gid: Some(metadata.gid()),
+    }
+}
+
+#[cfg(not(unix))]
+fn convert(metadata: &std::fs::Metadata, symlink: bool) -> Metadata {
+    Metadata {
+        kind: kind(metadata, symlink),
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        changed: None,
+        inode: None,
+        links: 1,
+        mode: None,
+        uid: None,
+        gid: None,
+    }
+}
+
+/// Returns where the symlink at `link` to `target` points, without going through the file system
+/// to resolve `..`
+pub(crate) fn resolve(link: &std::path::Path, target: &std::path::Path) -> std::path::PathBuf {
+    let mut resolved = link.parent().unwrap_or(link).to_path_buf();
+    for component in target.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                resolved.pop();
+            }
+            std::path::Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+fn kind(metadata: &std::fs::Metadata, symlink: bool) -> entry::Kind {
+    if symlink && metadata.is_symlink() {
+        entry::Kind::Symlink
+    } else if metadata.is_dir() {
+        entry::Kind::Directory
+    } else {
+        entry::Kind::File
+    }
+}
+
+/// A tree held in memory, for tests and for sources that are read whole
+///
+/// Parent directories are created along with every path, and the root `/` always exists
+#[derive(Debug, Default, Clone)]
+pub struct Memory {
+    nodes: std::collections::BTreeMap<std::path::PathBuf, Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    File {
+        content: std::sync::Arc<[u8]>,
+        modified: Option<std::time::SystemTime>,
+        attributes: Vec<(std::ffi::OsString, Vec<u8>)>,
+    },
+    Directory,
+    Symlink(std::path::PathBuf),
+}
+
+impl Memory {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file with the given `content` at `path`, replacing whatever was there
+    pub fn add_file(
+        &mut self,
+        path: impl Into<std::path::PathBuf>,
+        content: impl Into<Vec<u8>>,
+        modified: Option<std::time::SystemTime>,
+    ) -> &mut Self {
+        self.insert(
+            path.into(),
+            Node::File {
+                content: content.into().into(),
+                modified,
+                attributes: Vec::new(),
+            },
+        )
+    }
+
+    /// Sets an extended attribute of the file at `path`, which has no effect on other nodes
+    pub fn add_attribute(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        name: impl Into<std::ffi::OsString>,
+        value: impl Into<Vec<u8>>,
+    ) -> &mut Self {
+        if let Some(Node::File { attributes, .. }) = self.nodes.get_mut(path.as_ref()) {
+            attributes.push((name.into(), value.into()));
+        }
+        self
+    }
+
+    /// Adds an empty directory at `path`
+    pub fn add_directory(&mut self, path: impl Into<std::path::PathBuf>) -> &mut Self {
+        self.insert(path.into(), Node::Directory)
+    }
+
+    /// Adds a symlink at `path` to `target`, relative to the directory of the symlink unless
+    /// absolute
+    pub fn add_symlink(
+        &mut self,
+        path: impl Into<std::path::PathBuf>,
+        target: impl Into<std::path::PathBuf>,
+    ) -> &mut Self {
+        self.insert(path.into(), Node::Symlink(target.into()))
+    }
+
+    fn insert(&mut self, path: std::path::PathBuf, node: Node) -> &mut Self {
+        for parent in path.ancestors().skip(1) {
+            self.nodes
+                .entry(parent.to_path_buf())
+                .or_insert(Node::Directory);
+        }
+        self.nodes.insert(path, node);
+        self
+    }
+
+    fn node(&self, path: &std::path::Path) -> std::io::Result<&Node> {
+        if path == std::path::Path::new("/") {
+            return Ok(&Node::Directory);
+        }
+        self.nodes
+            .get(path)
+            .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+    }
+
+    /// Follows symlinks to the node at the end of the chain
+    fn follow(&self, path: &std::path::Path) -> std::io::Result<&Node> {
+        let mut path = path.to_path_buf();
+        // As many hops as the kernel follows before giving up with `ELOOP`
+        for _ in 0..40 {
+            match self.node(&path)? {
+                Node::Symlink(target) => path = resolve(&path, target),
+                node => return Ok(node),
+            }
+        }
+        Err(std::io::Error::other("too many levels of symbolic links"))
+    }
+}
+
+impl FileSystem for Memory {
+    type File = std::io::Cursor<std::sync::Arc<[u8]>>;
+
+    fn read_dir(&self, path: &std::path::Path) -> std::io::Result<Vec<std::path::PathBuf>> {
+        if !matches!(self.follow(path)?, Node::Directory) {
+            return Err(std::io::ErrorKind::NotADirectory.into());
+        }
+
+        Ok(self
+            .nodes
+            .range(path.to_path_buf()..)
+            .map(|(child, _)| child)
+            .take_while(|child| child.starts_with(path))
+            .filter(|child| child.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn metadata(&self, path: &std::path::Path) -> std::io::Result<Metadata> {
+        Ok(metadata(self.follow(path)?))
+    }
+
+    fn symlink_metadata(&self, path: &std::path::Path) -> std::io::Result<Metadata> {
+        Ok(metadata(self.node(path)?))
+    }
+
+    fn open(&self, path: &std::path::Path) -> std::io::Result<Self::File> {
+        match self.follow(path)? {
+            Node::File { content, .. } => Ok(std::io::Cursor::new(content.clone())),
+            Node::Directory | Node::Symlink(_) => Err(std::io::ErrorKind::IsADirectory.into()),
+        }
+    }
+
+    fn attributes(&self, path: &std::path::Path) -> std::io::Result<entry::Attributes> {
+        match self.follow(path)? {
+            Node::File { attributes, .. } => Ok(entry::Attributes::new(attributes.clone())),
+            Node::Directory | Node::Symlink(_) => Ok(entry::Attributes::new(Vec::new())),
+        }
+    }
+}
+
+fn metadata(node: &Node) -> Metadata {
+    let (kind, size, modified) = match node {
+        Node::File {
+            content, modified, ..
+        } => (entry::Kind::File, content.len() as u64, *modified),
+        Node::Directory => (entry::Kind::Directory, 0, None),
+        Node::Symlink(target) => (entry::Kind::Symlink, target.as_os_str().len() as u64, None),
+    };
+    Metadata {
+        kind,
+        size,
+        modified,
+        changed: None,
+        inode: None,
+        links: 1,
+        mode: None,
+        uid: None,
+        gid: None,
+    }
+}
@@ -0,0 +1,110 @@
+use super::{crawler, entry, options, source, thread};
+
+/// How a file differs from the manifest it is verified against
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Verdict {
+    /// The content changed while the size and modification time did not, which no regular write
+    /// does and hints at silent corruption
+    Corrupted,
+    /// The content changed along with the size or the modification time
+    Modified,
+    /// The file is only in the manifest
+    Missing,
+    /// The file is only in the directory
+    New,
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Corrupted => f.write_str("CORRUPTED"),
+            Verdict::Modified => f.write_str("MODIFIED"),
+            Verdict::Missing => f.write_str("MISSING"),
+            Verdict::New => f.write_str("NEW"),
+        }
+    }
+}
+
+/// Rehashes the directory at `path` and checks it against a `manifest` of the same directory
+/// created earlier by [`index`](crate::index), returning every path that does not match sorted
+/// by path
+///
+/// Every file is read again, the [`cache`](options::Options::cache) is ignored
+///
+/// # Errors
+///
+/// If the manifest was hashed with another [`algorithm`](options::Options::algorithm), otherwise
+/// see [`compare`](crate::compare)
+pub fn verify(
+    path: &std::path::Path,
+    manifest: &entry::Directory,
+    options: &options::Options,
+) -> Result<Vec<(std::path::PathBuf, Verdict)>, super::Error> {
+    if manifest.settings().algorithm != options.algorithm {
+        return Err(super::Error::Algorithms(
+            manifest.settings().algorithm,
+            options.algorithm,
+        ));
+    }
+    if *manifest.settings() != options::Settings::from(options) {
+        log::warn!(
+            "{} was indexed with different options, its hashes may not be comparable",
+            manifest.path().display(),
+        );
+    }
+
+    let pool = thread::pool()?;
+    // Never from the cache, corruption leaves the stamps it is keyed by untouched
+    let (entries, _) = crawler::crawl(
+        &std::sync::Arc::new(source::Local),
+        path,
+        options,
+        None,
+        None,
+        &pool,
+    )?;
+
+    log::info!("Starting verification");
+    let start = std::time::Instant::now();
+
+    let mut expected = manifest
+        .entries()
+        .iter()
+        .map(|e| (e.path(), e))
+        .collect::<std::collections::HashMap<_, _>>();
+
+    let mut verdicts = entries
+        .iter()
+        .filter_map(|entry| {
+            let Some(expected) = expected.remove(entry.path()) else {
+                return Some((entry.path.clone(), Verdict::New));
+            };
+
+            if entry.hash == expected.hash {
+                None
+            } else if entry.size == expected.size
+                && entry.modified.is_some()
+                && entry.modified == expected.modified
+            {
+                Some((entry.path.clone(), Verdict::Corrupted))
+            } else {
+                Some((entry.path.clone(), Verdict::Modified))
+            }
+        })
+        .collect::<Vec<_>>();
+    verdicts.extend(
+        expected
+            .into_keys()
+            .map(|path| (path.to_path_buf(), Verdict::Missing)),
+    );
+
+    verdicts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    log::info!(
+        "Finished verifying {} items in {:?}",
+        entries.len(),
+        start.elapsed()
+    );
+
+    Ok(verdicts)
+}
@@ -0,0 +1,198 @@
+//! Speaks the agent protocol to `dircmp agent`, with frames mirroring the private ones of the
+//! library: a big endian `u32` length followed by the bincode encoding
+
+mod common;
+
+use common::Temporary;
+use std::io::{Read, Write};
+
+const VERSION: u32 = 3;
+
+#[derive(Debug, serde::Serialize)]
+enum Request {
+    List,
+    Hash,
+    Quit,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum Response {
+    Hello {
+        version: u32,
+        path: std::path::PathBuf,
+    },
+    Listing {
+        files: u64,
+        bytes: u64,
+    },
+    Progress {
+        files: u64,
+        bytes: u64,
+    },
+    Entry(dircmp::Entry),
+    Done {
+        directories: Vec<std::path::PathBuf>,
+        settings: dircmp::Settings,
+    },
+    Error(String),
+}
+
+fn frame(frame: &impl serde::Serialize) -> Vec<u8> {
+    let frame = bincode::serialize(frame).unwrap();
+    let mut framed = u32::try_from(frame.len()).unwrap().to_be_bytes().to_vec();
+    framed.extend(frame);
+    framed
+}
+
+fn receive(reader: &mut impl Read) -> Response {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length).unwrap();
+    let mut frame = vec![0; u32::from_be_bytes(length) as usize];
+    reader.read_exact(&mut frame).unwrap();
+    bincode::deserialize(&frame).unwrap()
+}
+
+fn tree(name: &str) -> Temporary {
+    let directory = Temporary::new(name);
+    std::fs::write(directory.0.join("a.txt"), "alpha").unwrap();
+    std::fs::create_dir(directory.0.join("nested")).unwrap();
+    std::fs::write(directory.0.join("nested").join("b.txt"), "beta").unwrap();
+    directory
+}
+
+fn agent(path: &std::path::Path) -> std::process::Command {
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_dircmp"));
+    command.arg("agent").arg(path);
+    command
+}
+
+#[test]
+fn serves_a_directory() {
+    let directory = tree("serve");
+    let mut child = agent(&directory.0)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = child.stdin.take().unwrap();
+    let mut output = std::io::BufReader::new(child.stdout.take().unwrap());
+
+    match receive(&mut output) {
+        Response::Hello { version, path } => {
+            assert_eq!(version, VERSION);
+            assert_eq!(
+                path.canonicalize().unwrap(),
+                directory.0.canonicalize().unwrap()
+            );
+        }
+        response => panic!("Expected a hello, got {response:?}"),
+    }
+
+    input.write_all(&frame(&Request::List)).unwrap();
+    match receive(&mut output) {
+        Response::Listing { files, bytes } => assert_eq!((files, bytes), (2, 9)),
+        response => panic!("Expected a listing, got {response:?}"),
+    }
+
+    input.write_all(&frame(&Request::Hash)).unwrap();
+    let mut paths = Vec::new();
+    let directories = loop {
+        match receive(&mut output) {
+            Response::Progress { .. } => {}
+            Response::Entry(entry) => paths.push(entry.path().to_path_buf()),
+            Response::Done { directories, .. } => break directories,
+            response => panic!("Expected entries, got {response:?}"),
+        }
+    };
+    paths.sort_unstable();
+    assert_eq!(
+        paths,
+        [
+            std::path::Path::new("a.txt"),
+            std::path::Path::new("nested/b.txt")
+        ]
+    );
+    assert_eq!(directories, [std::path::Path::new("nested")]);
+
+    input.write_all(&frame(&Request::Quit)).unwrap();
+    drop(input);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn indexes_through_an_agent_like_locally() {
+    let directory = tree("remote");
+    let remote = dircmp::index_remote(agent(&directory.0)).unwrap();
+    let local = dircmp::index(directory.0.clone(), &dircmp::Options::default()).unwrap();
+
+    assert_eq!(remote.entries(), local.entries());
+    assert_eq!(remote.settings(), local.settings());
+}
+
+/// Starts a fake agent that sends the given `responses` whatever it is asked, and then waits for
+/// the client to hang up
+#[cfg(unix)]
+fn fake(name: &str, responses: &[Response]) -> (Temporary, std::process::Command) {
+    let directory = Temporary::new(name);
+    let script = directory.0.join("responses");
+    std::fs::write(
+        &script,
+        responses.iter().flat_map(frame).collect::<Vec<_>>(),
+    )
+    .unwrap();
+
+    let mut command = std::process::Command::new("sh");
+    command
+        .arg("-c")
+        .arg(r#"cat "$0" && cat > /dev/null"#)
+        .arg(script);
+    (directory, command)
+}
+
+#[cfg(unix)]
+#[test]
+fn rejects_other_versions() {
+    let (_directory, command) = fake(
+        "version",
+        &[Response::Hello {
+            version: VERSION + 1,
+            path: "/remote".into(),
+        }],
+    );
+
+    let error = dircmp::index_remote(command).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "Agent speaks protocol version {}, expected {VERSION}",
+            VERSION + 1
+        )
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn rejects_paths_outside_of_the_directory() {
+    for outside in ["../escaped", "/absolute"] {
+        let (_directory, command) = fake(
+            "outside",
+            &[
+                Response::Hello {
+                    version: VERSION,
+                    path: "/remote".into(),
+                },
+                Response::Listing { files: 0, bytes: 0 },
+                Response::Done {
+                    directories: vec![outside.into()],
+                    settings: dircmp::Settings::default(),
+                },
+            ],
+        );
+
+        let error = dircmp::index_remote(command).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Agent sent a path outside of its directory: `{outside}`")
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+const ALPHA: &str = "2c1743a391305fbf367df8e4f069f9f9";
+
+fn read(listing: &str) -> Result<dircmp::Directory, dircmp::Error> {
+    dircmp::read_checksums(listing.as_bytes(), "/listing".into())
+}
+
+fn hashes(directory: &dircmp::Directory) -> Vec<(&Path, String)> {
+    let mut hashes = directory
+        .entries()
+        .iter()
+        .map(|entry| (entry.path(), format!("{:x}", entry.hash())))
+        .collect::<Vec<_>>();
+    hashes.sort_unstable();
+    hashes
+}
+
+#[test]
+fn writes_and_reads_back_escaped_names() {
+    let mut fs = dircmp::Memory::new();
+    fs.add_file("/root/plain", "alpha", None)
+        .add_file("/root/back\\slash", "beta", None)
+        .add_file("/root/new\nline", "gamma", None)
+        .add_file("/root/nested/carriage\rreturn", "delta", None);
+    let directory = dircmp::index_in(
+        &std::sync::Arc::new(fs),
+        "/root".into(),
+        &dircmp::Options::default(),
+    )
+    .unwrap();
+
+    let mut listing = Vec::new();
+    dircmp::write_checksums(&directory, &mut listing).unwrap();
+    let listing = String::from_utf8(listing).unwrap();
+    assert!(listing.contains("  new\\nline\n"));
+    assert!(listing.contains(&format!("{ALPHA}  plain\n")));
+
+    let read = read(&listing).unwrap();
+    assert_eq!(hashes(&read), hashes(&directory));
+    assert_eq!(read.directories(), [Path::new("nested")]);
+}
+
+#[test]
+fn reads_bsd_style_and_binary_mode_lines() {
+    let sha256 = "8ed3f6ad685b959ead7022518e1af76cd816f8e8ec7ccdda1ed4018e8f2223f8";
+    let directory = read(&format!(
+        "SHA256 (a (1).txt) = {sha256}\n\\SHA256 (b\\nc) = {sha256}\n\n{sha256} *./d.bin\n"
+    ))
+    .unwrap();
+
+    assert_eq!(directory.settings().algorithm, dircmp::Algorithm::Sha256);
+    assert_eq!(
+        hashes(&directory),
+        [
+            (Path::new("a (1).txt"), String::from(sha256)),
+            (Path::new("b\nc"), String::from(sha256)),
+            (Path::new("d.bin"), String::from(sha256)),
+        ]
+    );
+}
+
+#[test]
+fn rejects_malformed_listings() {
+    let sha256 = "8ed3f6ad685b959ead7022518e1af76cd816f8e8ec7ccdda1ed4018e8f2223f8";
+    for (listing, error) in [
+        (
+            format!("{ALPHA}  a\n{sha256}  b\n"),
+            "Checksum on line 2 does not use the same algorithm as the previous ones",
+        ),
+        (
+            format!("{ALPHA}  a\n{ALPHA}  ./a\n"),
+            "Duplicate checksum for `a`",
+        ),
+        (
+            String::from("abcd  a\n"),
+            "Checksum on line 1 uses an unsupported algorithm",
+        ),
+        (format!("{ALPHA}a\n"), "Malformed checksum on line 1"),
+        (
+            format!("\\{ALPHA}  a\\tb\n"),
+            "Malformed checksum on line 1",
+        ),
+    ] {
+        assert_eq!(read(&listing).unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn leaves_kinds_out_of_the_comparison() {
+    let mut fs = dircmp::Memory::new();
+    fs.add_file("/root/a.txt", "alpha", None)
+        .add_symlink("/root/link", "a.txt");
+    let directory = dircmp::index_in(
+        &std::sync::Arc::new(fs),
+        "/root".into(),
+        &dircmp::Options::default(),
+    )
+    .unwrap();
+    let listing = read(&format!("{ALPHA}  a.txt\n{ALPHA}  link\n")).unwrap();
+
+    let (directory, _) = dircmp::link(directory, listing, &dircmp::Options::default()).unwrap();
+    assert!(
+        directory
+            .entries()
+            .iter()
+            .all(|entry| matches!(entry.status(), dircmp::Status::Same(_)))
+    );
+}
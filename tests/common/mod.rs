@@ -0,0 +1,16 @@
+/// A directory that is removed once the test is done with it
+pub struct Temporary(pub std::path::PathBuf);
+
+impl Temporary {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("dircmp-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for Temporary {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
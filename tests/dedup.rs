@@ -0,0 +1,60 @@
+mod common;
+
+use common::Temporary;
+
+fn dedup(args: &[&std::ffi::OsStr]) {
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_dircmp"))
+        .arg("dedup")
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[cfg(unix)]
+fn inode(path: &std::path::Path) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(&std::fs::metadata(path).unwrap())
+}
+
+#[test]
+fn undoes_replacements_from_the_journal() {
+    let directory = Temporary::new("dedup");
+    let tree = directory.0.join("tree");
+    let journal = directory.0.join("journal");
+    std::fs::create_dir(&tree).unwrap();
+    let (kept, replaced) = (tree.join("a.txt"), tree.join("b.txt"));
+    std::fs::write(&kept, "duplicate").unwrap();
+    std::fs::write(&replaced, "duplicate").unwrap();
+
+    // Before the epoch and off a whole second, which must survive the journal as is
+    let modified = std::time::UNIX_EPOCH - std::time::Duration::new(86_400, 250_000_000);
+    std::fs::File::options()
+        .write(true)
+        .open(&replaced)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+
+    dedup(&[
+        tree.as_os_str(),
+        "--method=hardlink".as_ref(),
+        "--journal".as_ref(),
+        journal.as_os_str(),
+    ]);
+    #[cfg(unix)]
+    assert_eq!(inode(&kept), inode(&replaced));
+    assert_eq!(
+        std::fs::read_to_string(&journal).unwrap().lines().count(),
+        1
+    );
+
+    dedup(&["--undo".as_ref(), "--journal".as_ref(), journal.as_os_str()]);
+    #[cfg(unix)]
+    assert_ne!(inode(&kept), inode(&replaced));
+    assert_eq!(std::fs::read_to_string(&replaced).unwrap(), "duplicate");
+    assert_eq!(
+        std::fs::metadata(&replaced).unwrap().modified().unwrap(),
+        modified
+    );
+}
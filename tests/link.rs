@@ -0,0 +1,90 @@
+mod common;
+
+use common::Temporary;
+use std::path::Path;
+
+fn index(fs: dircmp::Memory) -> dircmp::Directory {
+    dircmp::index_in(
+        &std::sync::Arc::new(fs),
+        "/".into(),
+        &dircmp::Options::default(),
+    )
+    .unwrap()
+}
+
+fn entry<'a>(directory: &'a dircmp::Directory, path: &str) -> &'a dircmp::Entry {
+    directory
+        .entries()
+        .iter()
+        .find(|entry| entry.path() == Path::new(path))
+        .unwrap_or_else(|| panic!("{path} is not indexed"))
+}
+
+#[test]
+fn ties_the_content_of_a_retyped_directory_to_the_change() {
+    let mut left = dircmp::Memory::new();
+    left.add_file("/docs", "a file", None)
+        .add_file("/kept", "kept", None);
+    let mut right = dircmp::Memory::new();
+    right
+        .add_file("/docs/one", "one", None)
+        .add_file("/docs/two", "two", None)
+        .add_file("/kept", "kept", None);
+
+    let (left, right) =
+        dircmp::link(index(left), index(right), &dircmp::Options::default()).unwrap();
+
+    assert_eq!(
+        *entry(&left, "docs").status(),
+        dircmp::Status::TypeChanged(dircmp::Kind::Directory)
+    );
+    for path in ["docs/one", "docs/two"] {
+        let entry = entry(&right, path);
+        assert_eq!(*entry.status(), dircmp::Status::Unique);
+        assert!(entry.flags().contains(dircmp::Flags::RETYPED));
+    }
+    assert!(
+        !entry(&right, "kept")
+            .flags()
+            .contains(dircmp::Flags::RETYPED)
+    );
+}
+
+/// A tree holding `log.txt` and an archive `a.tar` with a member `inner.txt`, both with `content`
+fn archived(directory: &Temporary, name: &str, content: &str) -> std::path::PathBuf {
+    let path = directory.0.join(name);
+    std::fs::create_dir(&path).unwrap();
+    std::fs::write(path.join("log.txt"), content).unwrap();
+
+    let mut archive = tar::Builder::new(std::fs::File::create(path.join("a.tar")).unwrap());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    archive
+        .append_data(&mut header, "inner.txt", content.as_bytes())
+        .unwrap();
+    archive.finish().unwrap();
+    path
+}
+
+#[test]
+fn reads_files_again_next_to_archive_members() {
+    let directory = Temporary::new("members");
+    let options = dircmp::Options {
+        into_archives: true,
+        prefixes: true,
+        ..dircmp::Options::default()
+    };
+    let left = dircmp::index(archived(&directory, "left", "abc"), &options).unwrap();
+    let right = dircmp::index(archived(&directory, "right", "abcdef"), &options).unwrap();
+
+    let (left, _) = dircmp::link(left, right, &options).unwrap();
+    assert!(matches!(
+        entry(&left, "log.txt").status(),
+        dircmp::Status::Appended(..)
+    ));
+    assert!(matches!(
+        entry(&left, "a.tar/inner.txt").status(),
+        dircmp::Status::Modified(..)
+    ));
+}
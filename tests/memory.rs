@@ -0,0 +1,84 @@
+use std::path::Path;
+
+fn tree() -> std::sync::Arc<dircmp::Memory> {
+    let mut fs = dircmp::Memory::new();
+    fs.add_file("/root/a.txt", "alpha", None)
+        .add_attribute("/root/a.txt", "user.tag", "first")
+        .add_file("/root/nested/b.txt", "beta", None)
+        .add_directory("/root/empty")
+        .add_symlink("/root/nested/link", "../a.txt")
+        .add_file("/outside.txt", "not indexed", None);
+    std::sync::Arc::new(fs)
+}
+
+fn entry<'a>(directory: &'a dircmp::Directory, path: &str) -> &'a dircmp::Entry {
+    directory
+        .entries()
+        .iter()
+        .find(|entry| entry.path() == Path::new(path))
+        .unwrap_or_else(|| panic!("{path} is not indexed"))
+}
+
+#[test]
+fn indexes_files_directories_and_symlinks() {
+    let directory = dircmp::index_in(&tree(), "/root".into(), &dircmp::Options::default()).unwrap();
+
+    let mut paths = directory
+        .entries()
+        .iter()
+        .map(dircmp::Entry::path)
+        .collect::<Vec<_>>();
+    paths.sort_unstable();
+    assert_eq!(
+        paths,
+        [
+            Path::new("a.txt"),
+            Path::new("nested/b.txt"),
+            Path::new("nested/link"),
+        ]
+    );
+    assert!(
+        directory
+            .directories()
+            .iter()
+            .any(|path| path == Path::new("empty"))
+    );
+
+    let file = entry(&directory, "a.txt");
+    assert_eq!(file.kind(), dircmp::Kind::File);
+    assert_eq!(file.size(), 5);
+    assert_eq!(
+        *file.hash(),
+        "2c1743a391305fbf367df8e4f069f9f9".parse().unwrap()
+    );
+    assert!(file.attributes().is_none());
+
+    let link = entry(&directory, "nested/link");
+    assert_eq!(link.kind(), dircmp::Kind::Symlink);
+    assert_eq!(link.hash(), file.hash());
+    assert_ne!(entry(&directory, "nested/b.txt").hash(), file.hash());
+}
+
+#[test]
+fn reads_attributes_when_asked() {
+    let options = dircmp::Options {
+        attributes: true,
+        ..dircmp::Options::default()
+    };
+    let directory = dircmp::index_in(&tree(), "/root".into(), &options).unwrap();
+
+    let attributes = entry(&directory, "a.txt")
+        .attributes()
+        .expect("Attributes are read")
+        .iter()
+        .collect::<Vec<_>>();
+    assert_eq!(
+        attributes,
+        [(std::ffi::OsStr::new("user.tag"), b"first".as_slice())]
+    );
+    assert!(
+        entry(&directory, "nested/b.txt")
+            .attributes()
+            .is_some_and(dircmp::Attributes::is_empty)
+    );
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use common::Temporary;
+use dircmp::{Conflict, Outcome};
+
+type Files = &'static [(&'static str, &'static str)];
+
+const BASE: Files = &[("a", "alpha"), ("b", "beta")];
+
+fn tree(files: Files) -> dircmp::Directory {
+    let mut fs = dircmp::Memory::new();
+    for (path, content) in files {
+        fs.add_file(format!("/{path}"), *content, None);
+    }
+    dircmp::index_in(
+        &std::sync::Arc::new(fs),
+        "/".into(),
+        &dircmp::Options::default(),
+    )
+    .unwrap()
+}
+
+/// Merges the trees, returning the outcome of each change along with the path it is known by
+fn merge(base: Files, left: Files, right: Files) -> Vec<(String, Outcome)> {
+    let merge = dircmp::link_three(
+        tree(base),
+        tree(left),
+        tree(right),
+        &dircmp::Options::default(),
+    )
+    .unwrap();
+    merge
+        .changes()
+        .iter()
+        .map(|change| {
+            let (base, left, right) = merge.entries(change);
+            let entry = base.or(left).or(right).unwrap();
+            (entry.path().display().to_string(), change.outcome())
+        })
+        .collect()
+}
+
+/// What `left` and `right` made of [`BASE`], and the outcomes expected by path
+struct Case {
+    name: &'static str,
+    left: Files,
+    right: Files,
+    expected: &'static [(&'static str, Outcome)],
+}
+
+fn check(cases: &[Case]) {
+    for case in cases {
+        let expected = case
+            .expected
+            .iter()
+            .map(|(path, outcome)| (String::from(*path), *outcome))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            merge(BASE, case.left, case.right),
+            expected,
+            "{}",
+            case.name
+        );
+    }
+}
+
+#[test]
+#[rustfmt::skip]
+fn takes_the_changes_of_either_side() {
+    check(&[
+        Case {
+            name: "unchanged",
+            left: BASE, right: BASE,
+            expected: &[("a", Outcome::Unchanged), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "left modified",
+            left: &[("a", "changed"), ("b", "beta")], right: BASE,
+            expected: &[("a", Outcome::Left), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "right moved",
+            left: BASE, right: &[("a", "alpha"), ("c", "beta")],
+            expected: &[("a", Outcome::Unchanged), ("b", Outcome::Right)],
+        },
+        Case {
+            name: "left deleted",
+            left: &[("b", "beta")], right: BASE,
+            expected: &[("a", Outcome::Left), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "left added",
+            left: &[("a", "alpha"), ("b", "beta"), ("c", "gamma")], right: BASE,
+            expected: &[("a", Outcome::Unchanged), ("b", Outcome::Unchanged), ("c", Outcome::Left)],
+        },
+        Case {
+            name: "both modified identically",
+            left: &[("a", "changed"), ("b", "beta")], right: &[("a", "changed"), ("b", "beta")],
+            expected: &[("a", Outcome::Both), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "both moved identically",
+            left: &[("c", "alpha"), ("b", "beta")], right: &[("c", "alpha"), ("b", "beta")],
+            expected: &[("a", Outcome::Both), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "both deleted",
+            left: &[("b", "beta")], right: &[("b", "beta")],
+            expected: &[("a", Outcome::Both), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "both added identically",
+            left: &[("a", "alpha"), ("b", "beta"), ("c", "gamma")],
+            right: &[("a", "alpha"), ("b", "beta"), ("c", "gamma")],
+            expected: &[("a", Outcome::Unchanged), ("b", Outcome::Unchanged), ("c", Outcome::Both)],
+        },
+        Case {
+            name: "left moved, right modified",
+            left: &[("c", "alpha"), ("b", "beta")], right: &[("a", "changed"), ("b", "beta")],
+            expected: &[("a", Outcome::Combined), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "left modified, right moved",
+            left: &[("a", "changed"), ("b", "beta")], right: &[("c", "alpha"), ("b", "beta")],
+            expected: &[("a", Outcome::Combined), ("b", Outcome::Unchanged)],
+        },
+    ]);
+}
+
+#[test]
+#[rustfmt::skip]
+fn reports_conflicting_changes() {
+    check(&[
+        Case {
+            name: "both modified differently",
+            left: &[("a", "left"), ("b", "beta")], right: &[("a", "right"), ("b", "beta")],
+            expected: &[("a", Outcome::Conflict(Conflict::Modified)), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "both moved differently",
+            left: &[("c", "alpha"), ("b", "beta")], right: &[("d", "alpha"), ("b", "beta")],
+            expected: &[("a", Outcome::Conflict(Conflict::Modified)), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "left deleted, right modified",
+            left: &[("b", "beta")], right: &[("a", "changed"), ("b", "beta")],
+            expected: &[("a", Outcome::Conflict(Conflict::Deleted)), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "left modified, right deleted",
+            left: &[("a", "changed"), ("b", "beta")], right: &[("b", "beta")],
+            expected: &[("a", Outcome::Conflict(Conflict::Deleted)), ("b", Outcome::Unchanged)],
+        },
+        Case {
+            name: "both added differently",
+            left: &[("a", "alpha"), ("b", "beta"), ("c", "left")],
+            right: &[("a", "alpha"), ("b", "beta"), ("c", "right")],
+            expected: &[
+                ("a", Outcome::Unchanged),
+                ("b", Outcome::Unchanged),
+                ("c", Outcome::Conflict(Conflict::Added)),
+            ],
+        },
+        Case {
+            name: "left moved onto what right added",
+            left: &[("c", "alpha"), ("b", "beta")],
+            right: &[("a", "alpha"), ("b", "beta"), ("c", "gamma")],
+            expected: &[
+                ("a", Outcome::Conflict(Conflict::Path)),
+                ("b", Outcome::Unchanged),
+                ("c", Outcome::Conflict(Conflict::Path)),
+            ],
+        },
+        Case {
+            name: "left moved onto what right added, right modified",
+            left: &[("c", "alpha"), ("b", "beta")],
+            right: &[("a", "changed"), ("b", "beta"), ("c", "gamma")],
+            expected: &[
+                ("a", Outcome::Conflict(Conflict::Path)),
+                ("b", Outcome::Unchanged),
+                ("c", Outcome::Conflict(Conflict::Path)),
+            ],
+        },
+        Case {
+            name: "both moved to the same path",
+            left: &[("c", "alpha"), ("b", "beta")], right: &[("a", "alpha"), ("c", "beta")],
+            expected: &[
+                ("a", Outcome::Conflict(Conflict::Path)),
+                ("b", Outcome::Conflict(Conflict::Path)),
+            ],
+        },
+    ]);
+}
+
+#[test]
+fn writes_edits_at_the_path_moved_to() {
+    let directory = Temporary::new("merge");
+    let write = |side: &str, files: Files| {
+        let path = directory.0.join(side);
+        std::fs::create_dir(&path).unwrap();
+        for (name, content) in files {
+            std::fs::write(path.join(name), content).unwrap();
+        }
+        path
+    };
+    let base = write("base", BASE);
+    let left = write("left", &[("c", "alpha"), ("b", "beta")]);
+    let right = write("right", &[("a", "changed"), ("b", "beta")]);
+    let target = write("target", BASE);
+
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_dircmp"))
+        .arg("merge")
+        .arg("--base")
+        .arg(&base)
+        .arg(&left)
+        .arg(&right)
+        .arg("--target")
+        .arg(&target)
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert!(!target.join("a").exists());
+    assert_eq!(std::fs::read_to_string(target.join("b")).unwrap(), "beta");
+    assert_eq!(
+        std::fs::read_to_string(target.join("c")).unwrap(),
+        "changed"
+    );
+}
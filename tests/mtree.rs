@@ -0,0 +1,122 @@
+use std::path::Path;
+
+const ALPHA: &str = "2c1743a391305fbf367df8e4f069f9f9";
+const BETA: &str = "987bcab01b929eb2c07877b224215c92";
+
+fn read(spec: &str) -> Result<dircmp::Directory, dircmp::Error> {
+    dircmp::read_mtree(spec.as_bytes(), "/spec".into())
+}
+
+fn entry<'a>(directory: &'a dircmp::Directory, path: &str) -> &'a dircmp::Entry {
+    directory
+        .entries()
+        .iter()
+        .find(|entry| entry.path() == Path::new(path))
+        .unwrap_or_else(|| panic!("{path} is not read"))
+}
+
+#[test]
+fn reads_defaults_nesting_full_paths_and_escapes() {
+    let directory = read(&format!(
+        r"#mtree
+/set type=file uid=0 gid=0 mode=0644
+. type=dir
+    a\040b size=5 md5digest={ALPHA}
+    sub type=dir mode=0755
+        c\134d md5digest={BETA}
+/unset gid
+        e \
+            md5digest={BETA}
+    ..
+    f mode=0600 uid=1000 md5digest={ALPHA}
+..
+./full/path/g md5digest={ALPHA}
+"
+    ))
+    .unwrap();
+
+    assert_eq!(directory.settings().algorithm, dircmp::Algorithm::Md5);
+    assert_eq!(
+        directory.directories(),
+        [Path::new("full"), Path::new("full/path"), Path::new("sub")]
+    );
+
+    let permissions = |path| {
+        let entry = entry(&directory, path);
+        (entry.mode(), entry.uid(), entry.gid())
+    };
+    assert_eq!(entry(&directory, "a b").size(), 5);
+    assert_eq!(permissions("a b"), (Some(0o644), Some(0), Some(0)));
+    assert_eq!(permissions("sub/c\\d"), (Some(0o644), Some(0), Some(0)));
+    assert_eq!(permissions("sub/e"), (Some(0o644), Some(0), None));
+    assert_eq!(permissions("f"), (Some(0o600), Some(1000), None));
+    assert_eq!(permissions("full/path/g"), (Some(0o644), Some(0), None));
+    assert_eq!(format!("{:x}", entry(&directory, "sub/e").hash()), BETA);
+}
+
+#[test]
+fn rejects_leaving_the_root() {
+    assert_eq!(
+        read(". type=dir\n..\n..\n").unwrap_err().to_string(),
+        "Unexpected `..` on line 3, already at the root"
+    );
+    assert_eq!(
+        read(&format!("a md5digest={ALPHA}\na md5digest={BETA}\n"))
+            .unwrap_err()
+            .to_string(),
+        "Duplicate mtree entry for `a`"
+    );
+    assert_eq!(
+        read("a md5digest\n").unwrap_err().to_string(),
+        "Malformed mtree spec on line 1"
+    );
+}
+
+#[test]
+fn keeps_the_default_algorithm_without_digests() {
+    let directory = read("#mtree\n. type=dir\n    sub type=dir\n    ..\n..\n").unwrap();
+    assert_eq!(directory.settings().algorithm, dircmp::Algorithm::default());
+    assert!(directory.entries().is_empty());
+}
+
+#[test]
+fn writes_and_reads_back_permissions() {
+    let directory = read(&format!(
+        "/set type=file\nodd\\tname mode=4755 uid=1 gid=2 md5digest={ALPHA}\nplain md5digest={BETA}\n"
+    ))
+    .unwrap();
+
+    let mut spec = Vec::new();
+    dircmp::write_mtree(&directory, &mut spec).unwrap();
+    let read = read(&String::from_utf8(spec).unwrap()).unwrap();
+
+    assert_eq!(read.entries().len(), 2);
+    for (path, permissions) in [
+        ("odd\tname", (Some(0o4755), Some(1), Some(2))),
+        ("plain", (None, None, None)),
+    ] {
+        let entry = entry(&read, path);
+        assert_eq!((entry.mode(), entry.uid(), entry.gid()), permissions);
+        assert_eq!(entry.hash(), self::entry(&directory, path).hash());
+    }
+}
+
+#[test]
+fn flags_differing_permissions_when_asked() {
+    let spec = |mode, uid: &str| read(&format!("a mode={mode} {uid} md5digest={ALPHA}\n")).unwrap();
+    let flagged = |left, right, permissions| {
+        let options = dircmp::Options {
+            permissions,
+            ..dircmp::Options::default()
+        };
+        let (left, _) = dircmp::link(left, right, &options).unwrap();
+        entry(&left, "a")
+            .flags()
+            .contains(dircmp::Flags::PERMISSIONS)
+    };
+
+    assert!(flagged(spec("0644", ""), spec("0600", ""), true));
+    assert!(!flagged(spec("0644", ""), spec("0600", ""), false));
+    assert!(!flagged(spec("0644", "uid=0"), spec("0644", ""), true));
+    assert!(flagged(spec("0644", "uid=0"), spec("0644", "uid=1"), true));
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::Temporary;
+
+fn tree(name: &str) -> Temporary {
+    let directory = Temporary::new(name);
+    std::fs::create_dir(directory.0.join("tree")).unwrap();
+    std::fs::write(directory.0.join("tree").join("a.txt"), "alpha").unwrap();
+    std::fs::write(directory.0.join("tree").join("b.txt.gz"), "not gzip").unwrap();
+    directory
+}
+
+fn sha256() -> dircmp::Options {
+    dircmp::Options {
+        algorithm: dircmp::Algorithm::Sha256,
+        ..dircmp::Options::default()
+    }
+}
+
+#[test]
+fn rejects_manifests_hashed_with_another_algorithm() {
+    let directory = tree("verify-algorithm");
+    let path = directory.0.join("tree");
+    let manifest = dircmp::index(path.clone(), &sha256()).unwrap();
+
+    let error = dircmp::verify(&path, &manifest, &dircmp::Options::default()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Cannot link directories hashed with SHA256 and MD5"
+    );
+    assert!(
+        dircmp::verify(&path, &manifest, &sha256())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn verifies_with_the_settings_of_the_manifest() {
+    let directory = tree("verify-settings");
+    let path = directory.0.join("tree");
+    let manifest = directory.0.join("manifest");
+    let summary = directory.0.join("summary");
+    let dircmp = |args: &[&std::ffi::OsStr]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_dircmp"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let index = dircmp(&[
+        "index".as_ref(),
+        "--digest=sha256".as_ref(),
+        "--decompress".as_ref(),
+        path.as_os_str(),
+        "--output".as_ref(),
+        manifest.as_os_str(),
+    ]);
+    assert!(index.status.success());
+
+    let verify = dircmp(&[
+        "verify".as_ref(),
+        path.as_os_str(),
+        manifest.as_os_str(),
+        "--summary".as_ref(),
+        summary.as_os_str(),
+    ]);
+    assert!(verify.status.success());
+    assert_eq!(std::fs::read_to_string(&summary).unwrap(), "");
+    assert!(
+        !String::from_utf8_lossy(&verify.stderr).contains("different options"),
+        "{}",
+        String::from_utf8_lossy(&verify.stderr)
+    );
+}
+
+#[test]
+fn keeps_times_before_the_epoch() {
+    let directory = tree("verify-epoch");
+    let path = directory.0.join("tree");
+    let manifest = directory.0.join("manifest");
+    let modified = std::time::UNIX_EPOCH - std::time::Duration::new(86_400, 250_000_000);
+    std::fs::File::options()
+        .write(true)
+        .open(path.join("a.txt"))
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+
+    let indexed = dircmp::index(path.clone(), &dircmp::Options::default()).unwrap();
+    let loaded: dircmp::Directory =
+        bincode::deserialize(&bincode::serialize(&indexed).unwrap()).unwrap();
+    let entry = loaded
+        .entries()
+        .iter()
+        .find(|entry| entry.path() == std::path::Path::new("a.txt"))
+        .unwrap();
+    assert_eq!(entry.modified(), Some(modified));
+
+    let dircmp = |args: &[&std::ffi::OsStr]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_dircmp"))
+            .args(args)
+            .status()
+            .unwrap()
+    };
+    assert!(
+        dircmp(&[
+            "index".as_ref(),
+            path.as_os_str(),
+            "--output".as_ref(),
+            manifest.as_os_str(),
+        ])
+        .success()
+    );
+    assert!(!directory.0.join("manifest.tmp").exists());
+    assert!(dircmp(&["verify".as_ref(), path.as_os_str(), manifest.as_os_str()]).success());
+}